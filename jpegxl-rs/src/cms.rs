@@ -0,0 +1,255 @@
+/*
+ * This file is part of jpegxl-rs.
+ *
+ * jpegxl-rs is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * jpegxl-rs is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with jpegxl-rs.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Color management system interface
+//!
+//! `libjxl` uses its own color management system by default. Implement [`Cms`] to replace it,
+//! for example to match the color conversions of the rest of an application.
+//!
+//! The wrapper takes care of the C interface: the conversion buffers of each thread are
+//! allocated after [`Cms::init`], and the returned [`CmsTransform`] is dropped when `libjxl`
+//! destroys it.
+
+use std::{
+    ffi::c_void,
+    mem::MaybeUninit,
+    ptr::{null_mut, slice_from_raw_parts},
+};
+
+use jpegxl_sys::{
+    color::{
+        cms::JxlGetDefaultCms,
+        cms_interface::{JxlCmsInterface, JxlColorProfile},
+        color_encoding::JxlColorEncoding,
+    },
+    common::types::JxlBool,
+};
+
+#[cfg(feature = "lcms2")]
+mod lcms;
+#[cfg(feature = "lcms2")]
+pub use lcms::Lcms2;
+
+/// Color profile of the input or output of a transform
+#[derive(Debug)]
+pub struct CmsProfile<'a> {
+    /// ICC profile
+    pub icc: &'a [u8],
+    /// Color encoding, which is only meaningful if the profile can be represented by it
+    pub color_encoding: &'a JxlColorEncoding,
+    /// Number of channels per pixel
+    pub num_channels: usize,
+}
+
+/// Color management system
+pub trait Cms: Sync {
+    /// Create a transform from `input` to `output`.
+    /// It is run from up to `num_threads` threads, with at most `pixels_per_thread` pixels each
+    /// time. `intensity_target` is the peak luminance of the image, in nits.
+    ///
+    /// Return `None` if the transform is not supported
+    fn init(
+        &self,
+        num_threads: usize,
+        pixels_per_thread: usize,
+        input: &CmsProfile,
+        output: &CmsProfile,
+        intensity_target: f32,
+    ) -> Option<Box<dyn CmsTransform>>;
+
+    /// Parse an ICC profile to a color encoding, and whether it is a CMYK profile.
+    /// Return `None` if it is invalid
+    ///
+    /// # Default
+    /// Use the default color management system of `libjxl`
+    fn set_fields_from_icc(&self, icc: &[u8]) -> Option<(JxlColorEncoding, bool)> {
+        let default = unsafe { &*JxlGetDefaultCms() };
+        let mut color_encoding = MaybeUninit::uninit();
+        let mut cmyk = JxlBool::False;
+        let res = (default.set_fields_from_icc)(
+            default.set_fields_data,
+            icc.as_ptr(),
+            icc.len(),
+            color_encoding.as_mut_ptr(),
+            &raw mut cmyk,
+        );
+
+        (res == JxlBool::True).then(|| {
+            (
+                unsafe { color_encoding.assume_init() },
+                cmyk == JxlBool::True,
+            )
+        })
+    }
+}
+
+/// Transform between two color profiles, created by [`Cms::init`]
+pub trait CmsTransform: Sync {
+    /// Convert the pixels in `input` to `output`.
+    /// Both are interleaved samples in the range of `0.0..=1.0`, with the number of channels of
+    /// their profiles. For CMYK, 0 represents the maximum amount of ink.
+    ///
+    /// It can be called concurrently from different `thread`s, each less than `num_threads`
+    /// given to [`Cms::init`]. Return `false` on failure.
+    fn run(&self, thread: usize, input: &[f32], output: &mut [f32]) -> bool;
+}
+
+/// State of a transform handed to `libjxl`
+struct Transform {
+    inner: Box<dyn CmsTransform>,
+    input_channels: usize,
+    output_channels: usize,
+    /// Buffers of all threads, `pixels_per_thread` pixels each
+    src_buf: Vec<f32>,
+    dst_buf: Vec<f32>,
+    pixels_per_thread: usize,
+}
+
+/// Create the C interface of a CMS.
+///
+/// `cms` must stay at the same address while `libjxl` uses the interface
+pub(crate) fn interface(cms: &&dyn Cms) -> JxlCmsInterface {
+    let data = std::ptr::from_ref(cms).cast_mut().cast();
+
+    JxlCmsInterface {
+        set_fields_data: data,
+        set_fields_from_icc,
+        init_data: data,
+        init,
+        get_src_buf,
+        get_dst_buf,
+        run,
+        destroy,
+    }
+}
+
+unsafe fn profile<'a>(profile: *const JxlColorProfile) -> CmsProfile<'a> {
+    let profile = unsafe { &*profile };
+    let icc = if profile.icc.data.is_null() {
+        &[]
+    } else {
+        unsafe { &*slice_from_raw_parts(profile.icc.data, profile.icc.size) }
+    };
+
+    CmsProfile {
+        icc,
+        color_encoding: &profile.color_encoding,
+        num_channels: profile.num_channels,
+    }
+}
+
+extern "C-unwind" fn set_fields_from_icc(
+    user_data: *mut c_void,
+    icc_data: *const u8,
+    icc_size: usize,
+    c: *mut JxlColorEncoding,
+    cmyk: *mut JxlBool,
+) -> JxlBool {
+    let cms = unsafe { *user_data.cast::<&dyn Cms>() };
+    let icc = unsafe { &*slice_from_raw_parts(icc_data, icc_size) };
+
+    match cms.set_fields_from_icc(icc) {
+        Some((color_encoding, is_cmyk)) => {
+            unsafe {
+                *c = color_encoding;
+                *cmyk = is_cmyk.into();
+            }
+            JxlBool::True
+        }
+        None => JxlBool::False,
+    }
+}
+
+extern "C-unwind" fn init(
+    init_data: *mut c_void,
+    num_threads: usize,
+    pixels_per_thread: usize,
+    input_profile: *const JxlColorProfile,
+    output_profile: *const JxlColorProfile,
+    intensity_target: f32,
+) -> *mut c_void {
+    let cms = unsafe { *init_data.cast::<&dyn Cms>() };
+    let (input, output) = unsafe { (profile(input_profile), profile(output_profile)) };
+
+    let Some(inner) = cms.init(
+        num_threads,
+        pixels_per_thread,
+        &input,
+        &output,
+        intensity_target,
+    ) else {
+        return null_mut();
+    };
+
+    let transform = Transform {
+        inner,
+        input_channels: input.num_channels,
+        output_channels: output.num_channels,
+        src_buf: vec![0.0; num_threads * pixels_per_thread * input.num_channels],
+        dst_buf: vec![0.0; num_threads * pixels_per_thread * output.num_channels],
+        pixels_per_thread,
+    };
+    Box::into_raw(Box::new(transform)).cast()
+}
+
+extern "C-unwind" fn get_src_buf(user_data: *mut c_void, thread: usize) -> *mut f32 {
+    let t = user_data.cast::<Transform>();
+    // Each thread gets its own part of the buffer, so no reference to the whole buffer is created
+    unsafe {
+        let offset = thread * (*t).pixels_per_thread * (*t).input_channels;
+        (*t).src_buf.as_mut_ptr().add(offset)
+    }
+}
+
+extern "C-unwind" fn get_dst_buf(user_data: *mut c_void, thread: usize) -> *mut f32 {
+    let t = user_data.cast::<Transform>();
+    unsafe {
+        let offset = thread * (*t).pixels_per_thread * (*t).output_channels;
+        (*t).dst_buf.as_mut_ptr().add(offset)
+    }
+}
+
+extern "C-unwind" fn run(
+    user_data: *mut c_void,
+    thread: usize,
+    input_buffer: *const f32,
+    output_buffer: *mut f32,
+    num_pixels: usize,
+) -> JxlBool {
+    let t = unsafe { &*user_data.cast::<Transform>() };
+    let input_len = num_pixels * t.input_channels;
+    let output_len = num_pixels * t.output_channels;
+
+    let res = if input_buffer == output_buffer.cast_const() {
+        // Convert in place
+        let input = unsafe { std::slice::from_raw_parts(input_buffer, input_len) }.to_vec();
+        let output = unsafe { std::slice::from_raw_parts_mut(output_buffer, output_len) };
+        t.inner.run(thread, &input, output)
+    } else {
+        let input = unsafe { std::slice::from_raw_parts(input_buffer, input_len) };
+        let output = unsafe { std::slice::from_raw_parts_mut(output_buffer, output_len) };
+        t.inner.run(thread, input, output)
+    };
+
+    res.into()
+}
+
+extern "C-unwind" fn destroy(user_data: *mut c_void) {
+    if !user_data.is_null() {
+        drop(unsafe { Box::from_raw(user_data.cast::<Transform>()) });
+    }
+}
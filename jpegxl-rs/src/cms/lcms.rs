@@ -0,0 +1,122 @@
+/*
+ * This file is part of jpegxl-rs.
+ *
+ * jpegxl-rs is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * jpegxl-rs is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with jpegxl-rs.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::mem::size_of_val;
+
+use jpegxl_sys::color::color_encoding::JxlRenderingIntent;
+use lcms2::{DisallowCache, Flags, GlobalContext, Intent, PixelFormat, Profile, Transform};
+
+use super::{Cms, CmsProfile, CmsTransform};
+
+/// Color management system backed by [Little CMS](https://www.littlecms.com)
+///
+/// # Example
+/// ```
+/// # || -> Result<(), Box<dyn std::error::Error>> {
+/// use jpegxl_rs::{cms::Lcms2, decoder_builder};
+///
+/// let decoder = decoder_builder().cms(&Lcms2).build()?;
+/// # Ok(())
+/// # };
+/// ```
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Lcms2;
+
+fn pixel_format(num_channels: usize) -> Option<PixelFormat> {
+    match num_channels {
+        1 => Some(PixelFormat::GRAY_FLT),
+        3 => Some(PixelFormat::RGB_FLT),
+        4 => Some(PixelFormat::CMYK_FLT),
+        _ => None,
+    }
+}
+
+impl Cms for Lcms2 {
+    fn init(
+        &self,
+        _num_threads: usize,
+        _pixels_per_thread: usize,
+        input: &CmsProfile,
+        output: &CmsProfile,
+        _intensity_target: f32,
+    ) -> Option<Box<dyn CmsTransform>> {
+        let in_format = pixel_format(input.num_channels)?;
+        let out_format = pixel_format(output.num_channels)?;
+        let in_profile = Profile::new_icc(input.icc).ok()?;
+        let out_profile = Profile::new_icc(output.icc).ok()?;
+
+        let intent = match output.color_encoding.rendering_intent {
+            JxlRenderingIntent::Perceptual => Intent::Perceptual,
+            JxlRenderingIntent::Relative => Intent::RelativeColorimetric,
+            JxlRenderingIntent::Saturation => Intent::Saturation,
+            JxlRenderingIntent::Absolute => Intent::AbsoluteColorimetric,
+        };
+
+        let transform = Transform::new_flags_context(
+            GlobalContext::new(),
+            &in_profile,
+            in_format,
+            &out_profile,
+            out_format,
+            intent,
+            Flags::NO_CACHE,
+        )
+        .ok()?;
+
+        Some(Box::new(LcmsTransform {
+            transform,
+            input_cmyk: input.num_channels == 4,
+            output_cmyk: output.num_channels == 4,
+        }))
+    }
+}
+
+struct LcmsTransform {
+    transform: Transform<u8, u8, GlobalContext, DisallowCache>,
+    input_cmyk: bool,
+    output_cmyk: bool,
+}
+
+fn as_bytes(s: &[f32]) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(s.as_ptr().cast(), size_of_val(s)) }
+}
+
+fn as_bytes_mut(s: &mut [f32]) -> &mut [u8] {
+    unsafe { std::slice::from_raw_parts_mut(s.as_mut_ptr().cast(), size_of_val(s)) }
+}
+
+impl CmsTransform for LcmsTransform {
+    fn run(&self, _thread: usize, input: &[f32], output: &mut [f32]) -> bool {
+        // Little CMS uses the amount of ink in percent for CMYK
+        if self.input_cmyk {
+            let input: Vec<_> = input.iter().map(|v| 100.0 * (1.0 - v)).collect();
+            self.transform
+                .transform_pixels(as_bytes(&input), as_bytes_mut(output));
+        } else {
+            self.transform
+                .transform_pixels(as_bytes(input), as_bytes_mut(output));
+        }
+
+        if self.output_cmyk {
+            for v in output.iter_mut() {
+                *v = 1.0 - *v / 100.0;
+            }
+        }
+
+        true
+    }
+}
@@ -17,6 +17,11 @@ along with jpegxl-rs.  If not, see <https://www.gnu.org/licenses/>.
 
 //! Common types used across the crate
 
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+
 use byteorder::{BE, ByteOrder, LE, NativeEndian};
 use half::f16;
 
@@ -25,6 +30,34 @@ use jpegxl_sys::common::types::{JxlDataType, JxlPixelFormat};
 /// Endianness of the pixels
 pub type Endianness = jpegxl_sys::common::types::JxlEndianness;
 
+/// Token to cancel a decoding or an encoding, possibly from another thread
+///
+/// Clones share the same state, so one can be given to the decoder or encoder and another
+/// kept to [`cancel`](Self::cancel) it. `libjxl` cannot be interrupted in the middle of its
+/// work, so the cancellation is noticed at the next opportunity, such as the next event of the
+/// decoder or the next write of the encoder.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// Create a token which is not cancelled
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel the decodings and encodings using this token
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether [`cancel`](Self::cancel) was called
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
 mod private {
     pub trait Sealed {}
 
@@ -17,19 +17,29 @@
 
 //! Decoder of JPEG XL format
 
-use std::{mem::MaybeUninit, ptr::null};
+use std::{
+    io::Read,
+    mem::MaybeUninit,
+    ptr::{NonNull, null},
+};
 
 use bon::bon;
 #[allow(clippy::wildcard_imports)]
 use jpegxl_sys::{
-    common::types::{JxlDataType, JxlPixelFormat},
+    color::color_encoding::{JxlColorSpace, JxlRenderingIntent},
+    common::types::{JxlBitDepth, JxlBitDepthType, JxlBool, JxlDataType, JxlPixelFormat},
     decode::*,
-    metadata::codestream_header::{JxlBasicInfo, JxlOrientation},
+    metadata::codestream_header::{
+        JxlAnimationHeader, JxlBasicInfo, JxlBlendInfo, JxlBlendMode, JxlExtraChannelType,
+        JxlLayerInfo, JxlOrientation, JxlPreviewHeader,
+    },
 };
 
 use crate::{
-    common::{Endianness, PixelType},
-    errors::{DecodeError, check_dec_status},
+    cms::{self, Cms},
+    common::{CancellationToken, Endianness, PixelType},
+    encode::ColorEncoding,
+    errors::{DecodeError, LimitKind, check_dec_status},
     memory::MemoryManager,
     parallel::ParallelRunner,
     utils::check_valid_signature,
@@ -38,12 +48,49 @@ use crate::{
 mod result;
 pub use result::*;
 
+mod input;
+pub(crate) use input::{Input, ReaderInput, SliceInput};
+
+mod state;
+use state::{DecodeState, ImageOut};
+
+mod callback;
+pub use callback::RowCallback;
+use callback::{FnRows, ImageOutCallback, ParallelRows};
+
+mod stream;
+pub use stream::*;
+
+mod frames;
+pub use frames::*;
+
+#[cfg(feature = "image")]
+mod steps;
+#[cfg(feature = "image")]
+pub(crate) use steps::Steps;
+
 /// Basic information
 pub type BasicInfo = JxlBasicInfo;
+/// Type of an extra channel
+pub type ExtraChannelType = JxlExtraChannelType;
+/// Preview image information
+pub type PreviewHeader = JxlPreviewHeader;
+/// Animation information
+pub type AnimationHeader = JxlAnimationHeader;
+/// Layer information
+pub type LayerInfo = JxlLayerInfo;
+/// Blending information of a layer
+pub type BlendInfo = JxlBlendInfo;
+/// Blend mode of a layer
+pub type BlendMode = JxlBlendMode;
 /// Progressive decoding steps
 pub type ProgressiveDetail = JxlProgressiveDetail;
 /// Orientation
 pub type Orientation = JxlOrientation;
+/// Color space of a color encoding
+pub type ColorSpace = JxlColorSpace;
+/// Rendering intent of a color encoding
+pub type RenderingIntent = JxlRenderingIntent;
 
 /// Desired Pixel Format
 #[derive(Clone, Copy, Debug)]
@@ -81,13 +128,39 @@ impl Default for PixelFormat {
     }
 }
 
+/// Color profile of the decoded pixels
+#[derive(Debug, Clone)]
+pub enum ColorProfile {
+    /// A color encoding, which is converted to an ICC profile if needed
+    Encoding(ColorEncoding),
+    /// An RGB or grayscale ICC profile
+    Icc(Vec<u8>),
+}
+
+/// Range of the decoded integer samples
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitDepth {
+    /// Use the full range of the pixel type, e.g. `0..=65535` for `u16`
+    FromPixelFormat,
+    /// Use the range of the bit depth of the image, e.g. `0..=1023` for a 10-bit image
+    FromCodestream,
+    /// Use the range of a custom number of bits per sample,
+    /// which cannot exceed the size of the pixel type
+    Custom(u32),
+}
+
 /// JPEG XL Decoder
-pub struct JxlDecoder<'pr, 'mm> {
+pub struct JxlDecoder<'pr, 'mm, 'cms> {
     /// Opaque pointer to the underlying decoder
     dec: *mut jpegxl_sys::decode::JxlDecoder,
 
     /// Override desired pixel format
     pub pixel_format: Option<PixelFormat>,
+    /// Range of the decoded integer samples. Float samples always range from `0.0` to `1.0`
+    ///
+    /// # Default
+    /// [`BitDepth::FromPixelFormat`]
+    pub bit_depth: Option<BitDepth>,
 
     /// Enables or disables preserving of as-in-bitstream pixel data orientation.
     /// If it is set to `true`, the decoder will skip applying the transformation
@@ -125,9 +198,24 @@ pub struct JxlDecoder<'pr, 'mm> {
     /// Configures whether to get boxes in raw mode or in decompressed mode.
     ///
     /// # Default
-    /// false, and the boxes are returned in raw mode
+    /// `true` if `libjxl` is built with Brotli, and `brob` boxes are decompressed transparently.
+    /// Otherwise, the boxes are returned in raw mode
     pub decompress: Option<bool>,
 
+    /// Preferred color encoding of the decoded pixels.
+    /// Only XYB encoded images are converted, and no color management system is needed.
+    /// Other images are returned in their original color space
+    ///
+    /// # Default
+    /// `None`, and the color encoding of the image is used
+    pub preferred_color_profile: Option<ColorEncoding>,
+    /// Color profile to convert the decoded pixels to, using the color management system.
+    /// Takes precedence over [`preferred_color_profile`](Self::preferred_color_profile)
+    ///
+    /// # Default
+    /// `None`, and the color encoding of the image is used
+    pub output_color_profile: Option<ColorProfile>,
+
     /// Configures at which progressive steps in frame decoding
     ///
     /// # Default
@@ -140,21 +228,75 @@ pub struct JxlDecoder<'pr, 'mm> {
     /// `false`
     pub icc_profile: bool,
 
+    /// Set if need metadata boxes, such as Exif and XMP
+    ///
+    /// # Default
+    /// `false`
+    pub metadata_boxes: bool,
+
     /// Set initial buffer for JPEG reconstruction
     /// Larger buffer could make reconstruction faster by doing fewer reallocation
     ///
     /// Default: 512 KiB
     pub init_jpeg_buffer: usize,
 
+    /// Maximum width of the image and its layers, checked before decoding
+    ///
+    /// # Default
+    /// `None`, and the width is not limited
+    pub max_width: Option<u32>,
+    /// Maximum height of the image and its layers, checked before decoding
+    ///
+    /// # Default
+    /// `None`, and the height is not limited
+    pub max_height: Option<u32>,
+    /// Maximum number of pixels of the image and its layers, checked before decoding
+    ///
+    /// # Default
+    /// `None`, and the number of pixels is not limited
+    pub max_pixels: Option<u64>,
+    /// Maximum number of frames, checked at each frame header
+    ///
+    /// # Default
+    /// `None`, and the number of frames is not limited
+    pub max_frames: Option<usize>,
+    /// Maximum number of extra channels, including the alpha channel
+    ///
+    /// # Default
+    /// `None`, and the number of extra channels is not limited
+    pub max_extra_channels: Option<u32>,
+    /// Maximum size of the ICC profile in bytes
+    ///
+    /// # Default
+    /// `None`, and the size is not limited
+    pub max_icc_size: Option<usize>,
+    /// Maximum number of bytes allocated for the outputs of a decoding, including the pixels
+    /// of every frame, extra channels, preview, JPEG reconstruction and metadata boxes.
+    /// Buffers reused between frames are counted once, and caller-provided buffers are not counted
+    ///
+    /// # Default
+    /// `None`, and the size is not limited
+    pub max_output_bytes: Option<usize>,
+
+    /// Token to cancel the decoding, checked between the events of the decoder and before
+    /// passing rows to the callbacks
+    pub cancel: Option<CancellationToken>,
+
     /// Set parallel runner
     pub parallel_runner: Option<&'pr dyn ParallelRunner>,
 
+    /// Set color management system, used for [`output_color_profile`](Self::output_color_profile)
+    ///
+    /// # Default
+    /// The color management system of `libjxl`
+    pub cms: Option<&'cms dyn Cms>,
+
     /// Set memory manager
     pub memory_manager: Option<&'mm dyn MemoryManager>,
 }
 
 #[bon]
-impl<'pr, 'mm> JxlDecoder<'pr, 'mm> {
+impl<'pr, 'mm, 'cms> JxlDecoder<'pr, 'mm, 'cms> {
     /// Build a [`JxlDecoder`]
     ///
     /// # Errors
@@ -162,16 +304,29 @@ impl<'pr, 'mm> JxlDecoder<'pr, 'mm> {
     #[builder(derive(Clone))]
     pub fn new(
         pixel_format: Option<PixelFormat>,
+        bit_depth: Option<BitDepth>,
         skip_reorientation: Option<bool>,
         unpremul_alpha: Option<bool>,
         render_spotcolors: Option<bool>,
         coalescing: Option<bool>,
         desired_intensity_target: Option<f32>,
         decompress: Option<bool>,
+        preferred_color_profile: Option<ColorEncoding>,
+        output_color_profile: Option<ColorProfile>,
         progressive_detail: Option<JxlProgressiveDetail>,
         #[builder(default)] icc_profile: bool,
+        #[builder(default)] metadata_boxes: bool,
         #[builder(default = 512 * 1024)] init_jpeg_buffer: usize,
+        max_width: Option<u32>,
+        max_height: Option<u32>,
+        max_pixels: Option<u64>,
+        max_frames: Option<usize>,
+        max_extra_channels: Option<u32>,
+        max_icc_size: Option<usize>,
+        max_output_bytes: Option<usize>,
+        cancel: Option<CancellationToken>,
         parallel_runner: Option<&'pr dyn ParallelRunner>,
+        cms: Option<&'cms dyn Cms>,
         memory_manager: Option<&'mm dyn MemoryManager>,
     ) -> Result<Self, DecodeError> {
         let dec = unsafe {
@@ -188,162 +343,98 @@ impl<'pr, 'mm> JxlDecoder<'pr, 'mm> {
         Ok(Self {
             dec,
             pixel_format,
+            bit_depth,
             skip_reorientation,
             unpremul_alpha,
             render_spotcolors,
             coalescing,
             desired_intensity_target,
             decompress,
+            preferred_color_profile,
+            output_color_profile,
             progressive_detail,
             icc_profile,
+            metadata_boxes,
             init_jpeg_buffer,
+            max_width,
+            max_height,
+            max_pixels,
+            max_frames,
+            max_extra_channels,
+            max_icc_size,
+            max_output_bytes,
+            cancel,
             parallel_runner,
+            cms,
             memory_manager,
         })
     }
 }
 
-impl JxlDecoder<'_, '_> {
+impl JxlDecoder<'_, '_, '_> {
     pub(crate) fn decode_internal(
         &self,
-        data: &[u8],
+        input: impl Input,
         data_type: Option<JxlDataType>,
         with_icc_profile: bool,
-        mut reconstruct_jpeg_buffer: Option<&mut Vec<u8>>,
+        reconstruct_jpeg_buffer: Option<&mut Vec<u8>>,
         format: *mut JxlPixelFormat,
         pixels: &mut Vec<u8>,
     ) -> Result<Metadata, DecodeError> {
-        let Some(sig) = check_valid_signature(data) else {
-            return Err(DecodeError::InvalidInput);
-        };
-        if !sig {
-            return Err(DecodeError::InvalidInput);
-        }
-
-        let mut basic_info = MaybeUninit::uninit();
-        let mut icc = if with_icc_profile { Some(vec![]) } else { None };
+        let mut state = DecodeState::new(
+            data_type,
+            with_icc_profile,
+            reconstruct_jpeg_buffer.is_some(),
+        );
+        self.run(input, &mut state)?;
 
-        self.setup_decoder(with_icc_profile, reconstruct_jpeg_buffer.is_some())?;
+        if let Some(buf) = reconstruct_jpeg_buffer {
+            *buf = state.jpeg_buffer.take().unwrap_or_default();
+        }
+        if let Some(pixel_format) = state.pixel_format {
+            unsafe { *format = pixel_format };
+        }
+        *pixels = std::mem::take(&mut state.pixels);
 
-        let next_in = data.as_ptr();
-        let avail_in = std::mem::size_of_val(data) as _;
+        state.metadata()
+    }
 
-        check_dec_status(unsafe { JxlDecoderSetInput(self.dec, next_in, avail_in) })?;
-        unsafe { JxlDecoderCloseInput(self.dec) };
+    /// Decode all of the input into `state`
+    fn run(&self, mut input: impl Input, state: &mut DecodeState) -> Result<(), DecodeError> {
+        state.boxes = self.metadata_boxes.then(Vec::new);
+        self.setup_decoder(state)?;
 
-        let mut status;
         loop {
-            use JxlDecoderStatus as s;
-
-            status = unsafe { JxlDecoderProcessInput(self.dec) };
-
-            match status {
-                s::NeedMoreInput | s::Error => return Err(DecodeError::GenericError),
-
-                // Get the basic info
-                s::BasicInfo => {
-                    check_dec_status(unsafe {
-                        JxlDecoderGetBasicInfo(self.dec, basic_info.as_mut_ptr())
-                    })?;
-
-                    if let Some(pr) = self.parallel_runner {
-                        pr.callback_basic_info(unsafe { &*basic_info.as_ptr() });
-                    }
-                }
-
-                // Get color encoding
-                s::ColorEncoding => {
-                    self.get_icc_profile(unsafe { icc.as_mut().unwrap_unchecked() })?;
-                }
-
-                // Get JPEG reconstruction buffer
-                s::JPEGReconstruction => {
-                    // Safety: JpegReconstruction is only called when reconstruct_jpeg_buffer
-                    // is not None
-                    let buf = unsafe { reconstruct_jpeg_buffer.as_mut().unwrap_unchecked() };
-                    buf.resize(self.init_jpeg_buffer, 0);
-                    check_dec_status(unsafe {
-                        JxlDecoderSetJPEGBuffer(self.dec, buf.as_mut_ptr(), buf.len())
-                    })?;
-                }
-
-                // JPEG buffer need more space
-                s::JPEGNeedMoreOutput => {
-                    // Safety: JpegNeedMoreOutput is only called when reconstruct_jpeg_buffer
-                    // is not None
-                    let buf = unsafe { reconstruct_jpeg_buffer.as_mut().unwrap_unchecked() };
-                    let need_to_write = unsafe { JxlDecoderReleaseJPEGBuffer(self.dec) };
-
-                    buf.resize(buf.len() + need_to_write, 0);
-                    check_dec_status(unsafe {
-                        JxlDecoderSetJPEGBuffer(self.dec, buf.as_mut_ptr(), buf.len())
-                    })?;
-                }
-
-                // Get the output buffer
-                s::NeedImageOutBuffer => {
-                    self.output(unsafe { &*basic_info.as_ptr() }, data_type, format, pixels)?;
-                }
-
-                // Informational events - continue processing
-                s::FullImage | s::Frame | s::FrameProgression => {}
-
-                s::Success => {
-                    if let Some(buf) = reconstruct_jpeg_buffer.as_mut() {
-                        let remaining = unsafe { JxlDecoderReleaseJPEGBuffer(self.dec) };
-
-                        buf.truncate(buf.len() - remaining);
-                        buf.shrink_to_fit();
-                    }
-
-                    unsafe { JxlDecoderReset(self.dec) };
-
-                    let info = unsafe { basic_info.assume_init() };
-                    return Ok(Metadata {
-                        width: info.xsize,
-                        height: info.ysize,
-                        intensity_target: info.intensity_target,
-                        min_nits: info.min_nits,
-                        orientation: info.orientation,
-                        num_color_channels: info.num_color_channels,
-                        has_alpha_channel: info.alpha_bits > 0,
-                        intrinsic_width: info.intrinsic_xsize,
-                        intrinsic_height: info.intrinsic_ysize,
-                        icc_profile: icc,
-                    });
-                }
-                // Features not yet implemented in this wrapper
-                s::NeedPreviewOutBuffer => {
-                    return Err(DecodeError::NotImplemented("preview image output"));
-                }
-                s::BoxNeedMoreOutput => return Err(DecodeError::NotImplemented("box output")),
-                s::PreviewImage => return Err(DecodeError::NotImplemented("preview image")),
-                s::Box => return Err(DecodeError::NotImplemented("box handling")),
-                s::BoxComplete => return Err(DecodeError::NotImplemented("box complete")),
+            match state.step(self)? {
+                JxlDecoderStatus::NeedMoreInput => input.more(self.dec)?,
+                JxlDecoderStatus::Success => break,
+                _ => {}
             }
         }
+        unsafe { JxlDecoderReset(self.dec) };
+
+        Ok(())
     }
 
-    fn setup_decoder(&self, icc: bool, reconstruct_jpeg: bool) -> Result<(), DecodeError> {
+    fn setup_decoder(&self, state: &mut DecodeState) -> Result<(), DecodeError> {
+        unsafe { JxlDecoderReset(self.dec) };
+
         if let Some(runner) = self.parallel_runner {
             check_dec_status(unsafe {
                 JxlDecoderSetParallelRunner(self.dec, runner.runner(), runner.as_opaque_ptr())
             })?;
         }
 
-        let events = {
-            use JxlDecoderStatus::{BasicInfo, ColorEncoding, FullImage, JPEGReconstruction};
-
-            let mut events = BasicInfo as i32 | FullImage as i32;
-            if icc {
-                events |= ColorEncoding as i32;
-            }
-            if reconstruct_jpeg {
-                events |= JPEGReconstruction as i32;
-            }
+        if let Some(cms) = &self.cms {
+            check_dec_status(unsafe { JxlDecoderSetCms(self.dec, cms::interface(cms)) })?;
+        }
 
-            events
-        };
+        // The frame header is also needed for the row size of layers
+        let coalescing = state.coalescing.or(self.coalescing).unwrap_or(true);
+        let mut events = state.events();
+        if self.limits_frames() || (!coalescing && state.strided_output()) {
+            events |= JxlDecoderStatus::Frame as i32;
+        }
         check_dec_status(unsafe { JxlDecoderSubscribeEvents(self.dec, events) })?;
 
         if let Some(val) = self.skip_reorientation {
@@ -355,21 +446,120 @@ impl JxlDecoder<'_, '_> {
         if let Some(val) = self.render_spotcolors {
             check_dec_status(unsafe { JxlDecoderSetRenderSpotcolors(self.dec, val.into()) })?;
         }
-        if let Some(val) = self.coalescing {
+        if let Some(val) = state.coalescing.or(self.coalescing) {
             check_dec_status(unsafe { JxlDecoderSetCoalescing(self.dec, val.into()) })?;
         }
         if let Some(val) = self.desired_intensity_target {
             check_dec_status(unsafe { JxlDecoderSetDesiredIntensityTarget(self.dec, val) })?;
         }
+        if let Some(val) = self.progressive_detail {
+            check_dec_status(unsafe { JxlDecoderSetProgressiveDetail(self.dec, val) })?;
+        }
+        if state.boxes.is_some() {
+            state.decompress_boxes = if let Some(val) = self.decompress {
+                check_dec_status(unsafe { JxlDecoderSetDecompressBoxes(self.dec, val.into()) })?;
+                val
+            } else {
+                // Fails if Brotli is not available
+                let status = unsafe { JxlDecoderSetDecompressBoxes(self.dec, JxlBool::True) };
+                status == JxlDecoderStatus::Success
+            };
+        }
 
         Ok(())
     }
 
+    /// Return [`DecodeError::Cancelled`] if the decoding is cancelled
+    fn check_cancelled(&self) -> Result<(), DecodeError> {
+        if self
+            .cancel
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+        {
+            Err(DecodeError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Whether the frame headers are needed to check the limits
+    fn limits_frames(&self) -> bool {
+        self.max_frames.is_some()
+            || self.max_width.is_some()
+            || self.max_height.is_some()
+            || self.max_pixels.is_some()
+    }
+
+    /// Check the dimensions of the image or a layer against the limits
+    fn check_size(&self, width: u32, height: u32) -> Result<(), DecodeError> {
+        check_limit(
+            LimitKind::Width,
+            self.max_width.map(u64::from),
+            width.into(),
+        )?;
+        check_limit(
+            LimitKind::Height,
+            self.max_height.map(u64::from),
+            height.into(),
+        )?;
+        check_limit(
+            LimitKind::Pixels,
+            self.max_pixels,
+            u64::from(width) * u64::from(height),
+        )
+    }
+
+    /// Account for `size` more bytes of output, checked against the limit
+    fn reserve_output(&self, output_bytes: &mut usize, size: usize) -> Result<(), DecodeError> {
+        *output_bytes += size;
+        check_limit(
+            LimitKind::OutputBytes,
+            self.max_output_bytes.map(|v| v as u64),
+            *output_bytes as u64,
+        )
+    }
+
+    /// Resize `pixels` to `size` bytes, only accounting for the growth of the allocation since
+    /// the buffer is reused between frames
+    fn resize_output(
+        &self,
+        pixels: &mut Vec<u8>,
+        size: usize,
+        output_bytes: &mut usize,
+    ) -> Result<(), DecodeError> {
+        self.reserve_output(output_bytes, size.saturating_sub(pixels.capacity()))?;
+        pixels.resize(size, 0);
+        Ok(())
+    }
+
+    fn set_color_profile(&self) -> Result<(), DecodeError> {
+        if let Some(encoding) = &self.preferred_color_profile {
+            check_dec_status(unsafe {
+                JxlDecoderSetPreferredColorProfile(self.dec, &encoding.into())
+            })?;
+        }
+
+        match &self.output_color_profile {
+            Some(ColorProfile::Encoding(encoding)) => check_dec_status(unsafe {
+                JxlDecoderSetOutputColorProfile(self.dec, &encoding.into(), null(), 0)
+            }),
+            Some(ColorProfile::Icc(icc)) => check_dec_status(unsafe {
+                JxlDecoderSetOutputColorProfile(self.dec, null(), icc.as_ptr(), icc.len())
+            }),
+            None => Ok(()),
+        }
+    }
+
     fn get_icc_profile(&self, icc_profile: &mut Vec<u8>) -> Result<(), DecodeError> {
         let mut icc_size = 0;
         check_dec_status(unsafe {
             JxlDecoderGetICCProfileSize(self.dec, JxlColorProfileTarget::Data, &raw mut icc_size)
         })?;
+        check_limit(
+            LimitKind::IccSize,
+            self.max_icc_size.map(|v| v as u64),
+            icc_size as u64,
+        )?;
         icc_profile.resize(icc_size, 0);
 
         check_dec_status(unsafe {
@@ -384,13 +574,23 @@ impl JxlDecoder<'_, '_> {
         Ok(())
     }
 
-    fn output(
+    pub(crate) fn get_color_encoding(
+        &self,
+        target: JxlColorProfileTarget,
+    ) -> Option<EncodedColorProfile> {
+        let mut color_encoding = MaybeUninit::uninit();
+        let status = unsafe {
+            JxlDecoderGetColorAsEncodedProfile(self.dec, target, color_encoding.as_mut_ptr())
+        };
+        (status == JxlDecoderStatus::Success)
+            .then(|| EncodedColorProfile::from(&unsafe { color_encoding.assume_init() }))
+    }
+
+    fn pixel_format(
         &self,
         info: &BasicInfo,
         data_type: Option<JxlDataType>,
-        format: *mut JxlPixelFormat,
-        pixels: &mut Vec<u8>,
-    ) -> Result<(), DecodeError> {
+    ) -> Result<JxlPixelFormat, DecodeError> {
         let data_type = match data_type {
             Some(v) => v,
             None => match (info.bits_per_sample, info.exponent_bits_per_sample) {
@@ -403,7 +603,7 @@ impl JxlDecoder<'_, '_> {
         };
 
         let f = self.pixel_format.unwrap_or_default();
-        let pixel_format = JxlPixelFormat {
+        Ok(JxlPixelFormat {
             num_channels: if f.num_channels == 0 {
                 info.num_color_channels + u32::from(info.alpha_bits > 0)
             } else {
@@ -412,36 +612,137 @@ impl JxlDecoder<'_, '_> {
             data_type,
             endianness: f.endianness,
             align: f.align,
+        })
+    }
+
+    fn output(
+        &self,
+        pixel_format: &JxlPixelFormat,
+        pixels: &mut Vec<u8>,
+        output_bytes: &mut usize,
+    ) -> Result<(), DecodeError> {
+        let mut size = 0;
+        check_dec_status(unsafe {
+            JxlDecoderImageOutBufferSize(self.dec, pixel_format, &raw mut size)
+        })?;
+        self.resize_output(pixels, size, output_bytes)?;
+
+        check_dec_status(unsafe {
+            JxlDecoderSetImageOutBuffer(self.dec, pixel_format, pixels.as_mut_ptr().cast(), size)
+        })
+    }
+
+    /// Set the bit depth of the image output, which must be set already
+    fn set_bit_depth(&self, pixel_format: &JxlPixelFormat) -> Result<(), DecodeError> {
+        let Some(bit_depth) = self.bit_depth else {
+            return Ok(());
+        };
+        // Only the default is supported for float pixel types
+        if matches!(
+            pixel_format.data_type,
+            JxlDataType::Float | JxlDataType::Float16
+        ) {
+            return Ok(());
+        }
+
+        let (r#type, bits_per_sample) = match bit_depth {
+            BitDepth::FromPixelFormat => (JxlBitDepthType::FromPixelFormat, 0),
+            BitDepth::FromCodestream => (JxlBitDepthType::FromCodestream, 0),
+            BitDepth::Custom(bits) => (JxlBitDepthType::Custom, bits),
+        };
+        let bit_depth = JxlBitDepth {
+            r#type,
+            bits_per_sample,
+            exponent_bits_per_sample: 0,
         };
+        check_dec_status(unsafe { JxlDecoderSetImageOutBitDepth(self.dec, &raw const bit_depth) })
+    }
+
+    /// Set the caller-provided `buffer` as the output, with rows `stride` bytes apart.
+    /// `width` is the width of the output, which is the layer width without coalescing
+    fn output_into(
+        &self,
+        width: Option<u32>,
+        pixel_format: &mut JxlPixelFormat,
+        stride: Option<usize>,
+        buffer: NonNull<[u8]>,
+    ) -> Result<(), DecodeError> {
+        pixel_format.endianness = Endianness::Native;
 
         let mut size = 0;
+        if let Some(stride) = stride {
+            let width = width.ok_or(DecodeError::InternalError("Frame header not available"))?;
+            let row = width as usize
+                * pixel_format.num_channels as usize
+                * bytes_per_sample(pixel_format.data_type);
+            if stride < row {
+                return Err(DecodeError::InvalidStride { stride, row });
+            }
+            // Rows are padded to exactly the stride when it is not shorter than a row
+            pixel_format.align = stride;
+        }
+
         check_dec_status(unsafe {
-            JxlDecoderImageOutBufferSize(self.dec, &raw const pixel_format, &raw mut size)
+            JxlDecoderImageOutBufferSize(self.dec, pixel_format, &raw mut size)
         })?;
-        pixels.resize(size, 0);
+        if buffer.len() < size {
+            return Err(DecodeError::BufferTooSmall {
+                expected: size,
+                actual: buffer.len(),
+            });
+        }
 
         check_dec_status(unsafe {
-            JxlDecoderSetImageOutBuffer(
+            JxlDecoderSetImageOutBuffer(self.dec, pixel_format, buffer.as_ptr().cast(), size)
+        })
+    }
+
+    fn extra_channel_output(
+        &self,
+        pixel_format: &JxlPixelFormat,
+        index: u32,
+        pixels: &mut Vec<u8>,
+        output_bytes: &mut usize,
+    ) -> Result<(), DecodeError> {
+        let mut size = 0;
+        check_dec_status(unsafe {
+            JxlDecoderExtraChannelBufferSize(self.dec, pixel_format, &raw mut size, index)
+        })?;
+        self.resize_output(pixels, size, output_bytes)?;
+
+        check_dec_status(unsafe {
+            JxlDecoderSetExtraChannelBuffer(
                 self.dec,
-                &raw const pixel_format,
+                pixel_format,
                 pixels.as_mut_ptr().cast(),
                 size,
+                index,
             )
+        })
+    }
+
+    fn preview_output(
+        &self,
+        pixel_format: &JxlPixelFormat,
+        pixels: &mut Vec<u8>,
+        output_bytes: &mut usize,
+    ) -> Result<(), DecodeError> {
+        let mut size = 0;
+        check_dec_status(unsafe {
+            JxlDecoderPreviewOutBufferSize(self.dec, pixel_format, &raw mut size)
         })?;
+        self.resize_output(pixels, size, output_bytes)?;
 
-        unsafe { *format = pixel_format };
-        Ok(())
+        check_dec_status(unsafe {
+            JxlDecoderSetPreviewOutBuffer(self.dec, pixel_format, pixels.as_mut_ptr().cast(), size)
+        })
     }
 
-    /// Decode a JPEG XL image
-    ///
-    /// # Errors
-    /// Return a [`DecodeError`] when internal decoder fails
-    pub fn decode(&self, data: &[u8]) -> Result<(Metadata, Pixels), DecodeError> {
+    fn decode_input(&self, input: impl Input) -> Result<(Metadata, Pixels), DecodeError> {
         let mut buffer = vec![];
         let mut pixel_format = MaybeUninit::uninit();
         let metadata = self.decode_internal(
-            data,
+            input,
             None,
             self.icc_profile,
             None,
@@ -454,18 +755,14 @@ impl JxlDecoder<'_, '_> {
         ))
     }
 
-    /// Decode a JPEG XL image to a specific pixel type
-    ///
-    /// # Errors
-    /// Return a [`DecodeError`] when internal decoder fails
-    pub fn decode_with<T: PixelType>(
+    fn decode_input_with<T: PixelType>(
         &self,
-        data: &[u8],
+        input: impl Input,
     ) -> Result<(Metadata, Vec<T>), DecodeError> {
         let mut buffer = vec![];
         let mut pixel_format = MaybeUninit::uninit();
         let metadata = self.decode_internal(
-            data,
+            input,
             Some(T::pixel_type()),
             self.icc_profile,
             None,
@@ -483,19 +780,42 @@ impl JxlDecoder<'_, '_> {
         Ok((metadata, buf))
     }
 
-    /// Reconstruct JPEG data. Fallback to pixels if JPEG reconstruction fails
-    ///
-    /// # Note
-    /// You can reconstruct JPEG data or get pixels in one go
-    ///
-    /// # Errors
-    /// Return a [`DecodeError`] when internal decoder fails
-    pub fn reconstruct(&self, data: &[u8]) -> Result<(Metadata, Data), DecodeError> {
+    /// Decode pixels of type `T` to `image_out`
+    fn decode_input_to<T: PixelType>(
+        &self,
+        input: impl Input,
+        image_out: ImageOut,
+    ) -> Result<Metadata, DecodeError> {
+        let mut state = DecodeState::new(Some(T::pixel_type()), self.icc_profile, false);
+        state.image_out = image_out;
+        self.run(input, &mut state)?;
+
+        state.metadata()
+    }
+
+    fn decode_input_into<T: PixelType>(
+        &self,
+        input: impl Input,
+        buffer: &mut [T],
+        stride: Option<usize>,
+    ) -> Result<Metadata, DecodeError> {
+        // Pixel types are plain numbers, so the buffer can be written as bytes
+        let image_out = ImageOut::Buffer {
+            buffer: NonNull::slice_from_raw_parts(
+                NonNull::from(&mut *buffer).cast(),
+                size_of_val(buffer),
+            ),
+            stride: stride.map(|v| v * size_of::<T>()),
+        };
+        self.decode_input_to::<T>(input, image_out)
+    }
+
+    fn reconstruct_input(&self, input: impl Input) -> Result<(Metadata, Data), DecodeError> {
         let mut buffer = vec![];
         let mut pixel_format = MaybeUninit::uninit();
         let mut jpeg_buf = vec![];
         let metadata = self.decode_internal(
-            data,
+            input,
             None,
             self.icc_profile,
             Some(&mut jpeg_buf),
@@ -512,9 +832,440 @@ impl JxlDecoder<'_, '_> {
             },
         ))
     }
+
+    /// Read the header information of a JPEG XL image, without decoding the pixels.
+    /// `data` only needs to contain the image header and the color encoding.
+    ///
+    /// # Errors
+    /// Return a [`DecodeError`] when internal decoder fails
+    pub fn probe(&self, data: &[u8]) -> Result<ImageInfo, DecodeError> {
+        let mut input = SliceInput::new(data);
+        let mut state = DecodeState::new(None, false, false);
+        self.setup_decoder(&mut state)?;
+
+        loop {
+            match state.step(self)? {
+                JxlDecoderStatus::NeedMoreInput => input.more(self.dec)?,
+                JxlDecoderStatus::ColorEncoding => break,
+                JxlDecoderStatus::Success => {
+                    return Err(DecodeError::InternalError("Color encoding not available"));
+                }
+                _ => {}
+            }
+        }
+        unsafe { JxlDecoderReset(self.dec) };
+
+        state.image_info()
+    }
+
+    /// Decode a JPEG XL image
+    ///
+    /// # Errors
+    /// Return a [`DecodeError`] when internal decoder fails
+    pub fn decode(&self, data: &[u8]) -> Result<(Metadata, Pixels), DecodeError> {
+        self.decode_input(SliceInput::new(data))
+    }
+
+    /// Decode a JPEG XL image to a specific pixel type
+    ///
+    /// # Errors
+    /// Return a [`DecodeError`] when internal decoder fails
+    pub fn decode_with<T: PixelType>(
+        &self,
+        data: &[u8],
+    ) -> Result<(Metadata, Vec<T>), DecodeError> {
+        self.decode_input_with(SliceInput::new(data))
+    }
+
+    /// Decode a JPEG XL image directly into `buffer`, without allocating for the pixels.
+    ///
+    /// `stride` is the number of samples from the start of a row to the next, or `None` for
+    /// tightly packed rows. Samples are in native endianness, and the number of channels is
+    /// the same as [`decode_with`](Self::decode_with). A buffer larger than needed is allowed,
+    /// and the rest is left untouched.
+    ///
+    /// # Errors
+    /// Return [`DecodeError::BufferTooSmall`] if the buffer cannot hold the image,
+    /// [`DecodeError::InvalidStride`] if `stride` is shorter than a row,
+    /// or other [`DecodeError`] when internal decoder fails
+    pub fn decode_into<T: PixelType>(
+        &self,
+        data: &[u8],
+        buffer: &mut [T],
+        stride: Option<usize>,
+    ) -> Result<Metadata, DecodeError> {
+        self.decode_input_into(SliceInput::new(data), buffer, stride)
+    }
+
+    /// Decode a JPEG XL image, passing the pixels to `callback` row by row instead of keeping
+    /// the whole image in memory.
+    ///
+    /// `callback` receives the position (`x`, `y`) of the first pixel, and the interleaved
+    /// samples of part of a row in native endianness. With a parallel runner, the rows arrive
+    /// in no particular order, and the calls are serialized. Use
+    /// [`decode_rows_parallel`](Self::decode_rows_parallel) for concurrent calls.
+    ///
+    /// # Note
+    /// Panicking in `callback` aborts the process
+    ///
+    /// # Errors
+    /// Return a [`DecodeError`] when internal decoder fails
+    pub fn decode_rows<T: PixelType>(
+        &self,
+        data: &[u8],
+        callback: impl FnMut(usize, usize, &[T]) + Send,
+    ) -> Result<Metadata, DecodeError> {
+        let mut rows = FnRows::new(callback, self.cancel.clone());
+        let image_out = ImageOut::Callback(rows.image_out());
+        self.decode_input_to::<T>(SliceInput::new(data), image_out)
+    }
+
+    /// Decode a JPEG XL image, passing the pixels to `callback` row by row from multiple
+    /// threads of the parallel runner.
+    /// See [`decode_rows`](Self::decode_rows) and [`RowCallback`] for details.
+    ///
+    /// # Note
+    /// Panicking in `callback` aborts the process
+    ///
+    /// # Errors
+    /// Return a [`DecodeError`] when internal decoder fails
+    pub fn decode_rows_parallel<T: PixelType, C: RowCallback<T>>(
+        &self,
+        data: &[u8],
+        callback: &mut C,
+    ) -> Result<Metadata, DecodeError> {
+        let mut rows = ParallelRows::new(callback, self.cancel.clone());
+        let image_out = ImageOut::Callback(rows.image_out());
+        self.decode_input_to::<T>(SliceInput::new(data), image_out)
+    }
+
+    /// Decode a JPEG XL image, and each extra channel to its own plane
+    ///
+    /// The planes are in the same order as [`Metadata::extra_channels`], and are upsampled to the
+    /// image dimensions. The alpha channel is also an extra channel, so it is in both the
+    /// interleaved pixels and its own plane.
+    ///
+    /// # Errors
+    /// Return a [`DecodeError`] when internal decoder fails
+    pub fn decode_extra_channels(
+        &self,
+        data: &[u8],
+    ) -> Result<(Metadata, Pixels, Vec<Pixels>), DecodeError> {
+        let (metadata, format, pixels, planes) = self.extra_channels_internal(data, None)?;
+        let planes = planes
+            .into_iter()
+            .map(|plane| Pixels::new(plane, &format))
+            .collect();
+
+        Ok((metadata, Pixels::new(pixels, &format), planes))
+    }
+
+    /// Decode a JPEG XL image to a specific pixel type, and each extra channel to its own plane.
+    /// See [`decode_extra_channels`](Self::decode_extra_channels) for details.
+    ///
+    /// # Errors
+    /// Return a [`DecodeError`] when internal decoder fails
+    #[allow(clippy::type_complexity)]
+    pub fn decode_extra_channels_with<T: PixelType>(
+        &self,
+        data: &[u8],
+    ) -> Result<(Metadata, Vec<T>, Vec<Vec<T>>), DecodeError> {
+        let (metadata, format, pixels, planes) =
+            self.extra_channels_internal(data, Some(T::pixel_type()))?;
+        let planes = planes
+            .iter()
+            .map(|plane| T::convert(plane, &format))
+            .collect();
+
+        Ok((metadata, T::convert(&pixels, &format), planes))
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn extra_channels_internal(
+        &self,
+        data: &[u8],
+        data_type: Option<JxlDataType>,
+    ) -> Result<(Metadata, JxlPixelFormat, Vec<u8>, Vec<Vec<u8>>), DecodeError> {
+        let mut state = DecodeState::new(data_type, self.icc_profile, false);
+        state.extra_channels = Some(vec![]);
+        self.run(SliceInput::new(data), &mut state)?;
+
+        let format = state
+            .pixel_format
+            .ok_or(DecodeError::InternalError("Pixel format not available"))?;
+        Ok((
+            state.metadata()?,
+            format,
+            std::mem::take(&mut state.pixels),
+            state.extra_channels.take().unwrap_or_default(),
+        ))
+    }
+
+    /// Decode a JPEG XL image progressively to a specific pixel type
+    ///
+    /// `callback` is called with the pixels rendered so far and the intended downsampling ratio
+    /// (1, 2, 4 or 8) every time a step set by [`progressive_detail`](Self::progressive_detail)
+    /// is reached. If the input is truncated, `callback` is called once more with whatever can be
+    /// rendered before the error is returned.
+    ///
+    /// # Errors
+    /// Return a [`DecodeError`] when internal decoder fails
+    pub fn decode_progressive<T: PixelType>(
+        &self,
+        data: &[u8],
+        mut callback: impl FnMut(&[T], usize),
+    ) -> Result<(Metadata, Vec<T>), DecodeError> {
+        if check_valid_signature(data) != Some(true) {
+            return Err(DecodeError::InvalidInput);
+        }
+
+        let mut state = DecodeState::new(Some(T::pixel_type()), self.icc_profile, false);
+        state.boxes = self.metadata_boxes.then(Vec::new);
+        state.subscribe(JxlDecoderStatus::FrameProgression);
+        self.setup_decoder(&mut state)?;
+
+        // Input is not closed at first, so truncated input is reported as `NeedMoreInput`
+        // and can be flushed
+        check_dec_status(unsafe { JxlDecoderSetInput(self.dec, data.as_ptr(), data.len()) })?;
+
+        let mut render = |state: &DecodeState| {
+            if let Some(format) = state.pixel_format.filter(|_| state.flush(self)) {
+                let ratio = unsafe { JxlDecoderGetIntendedDownsamplingRatio(self.dec) };
+                callback(&T::convert(&state.pixels, &format), ratio);
+            }
+        };
+
+        let mut closed = false;
+        loop {
+            match state.step(self)? {
+                JxlDecoderStatus::FrameProgression => render(&state),
+                // All input is consumed, the decoder fails afterwards if it is truncated
+                JxlDecoderStatus::NeedMoreInput if !closed => {
+                    render(&state);
+                    unsafe { JxlDecoderCloseInput(self.dec) };
+                    closed = true;
+                }
+                JxlDecoderStatus::NeedMoreInput => return Err(DecodeError::GenericError),
+                JxlDecoderStatus::Success => break,
+                _ => {}
+            }
+        }
+        unsafe { JxlDecoderReset(self.dec) };
+
+        let format = state
+            .pixel_format
+            .ok_or(DecodeError::InternalError("Pixel format not available"))?;
+        let buf = T::convert(&state.pixels, &format);
+
+        Ok((state.metadata()?, buf))
+    }
+
+    /// Decode the preview image embedded in a JPEG XL image.
+    /// Decoding stops right after the preview, so the main image is never decoded.
+    ///
+    /// Return `None` if the image has no preview. Its dimensions are in the returned
+    /// [`PreviewHeader`].
+    ///
+    /// # Errors
+    /// Return a [`DecodeError`] when internal decoder fails
+    pub fn decode_preview(
+        &self,
+        data: &[u8],
+    ) -> Result<Option<(PreviewHeader, Pixels)>, DecodeError> {
+        Ok(self
+            .preview_internal(data, None)?
+            .map(|(header, buffer, format)| (header, Pixels::new(buffer, &format))))
+    }
+
+    /// Decode the preview image embedded in a JPEG XL image to a specific pixel type.
+    /// See [`decode_preview`](Self::decode_preview) for details.
+    ///
+    /// # Errors
+    /// Return a [`DecodeError`] when internal decoder fails
+    pub fn decode_preview_with<T: PixelType>(
+        &self,
+        data: &[u8],
+    ) -> Result<Option<(PreviewHeader, Vec<T>)>, DecodeError> {
+        Ok(self
+            .preview_internal(data, Some(T::pixel_type()))?
+            .map(|(header, buffer, format)| (header, T::convert(&buffer, &format))))
+    }
+
+    fn preview_internal(
+        &self,
+        data: &[u8],
+        data_type: Option<JxlDataType>,
+    ) -> Result<Option<(PreviewHeader, Vec<u8>, JxlPixelFormat)>, DecodeError> {
+        let mut input = SliceInput::new(data);
+        let mut state = DecodeState::new(data_type, false, false);
+        state.preview = Some(vec![]);
+        self.setup_decoder(&mut state)?;
+
+        let header = loop {
+            match state.step(self)? {
+                JxlDecoderStatus::NeedMoreInput => input.more(self.dec)?,
+                // No need to decode further without a preview
+                JxlDecoderStatus::BasicInfo
+                    if state
+                        .basic_info
+                        .as_ref()
+                        .is_some_and(|info| info.have_preview == JxlBool::False) =>
+                {
+                    break None;
+                }
+                JxlDecoderStatus::PreviewImage => {
+                    break state.basic_info.as_ref().map(|info| info.preview.clone());
+                }
+                JxlDecoderStatus::Success => break None,
+                _ => {}
+            }
+        };
+        unsafe { JxlDecoderReset(self.dec) };
+
+        Ok(header
+            .zip(state.preview_format)
+            .map(|(header, format)| (header, state.preview.take().unwrap_or_default(), format)))
+    }
+
+    /// Reconstruct JPEG data. Fallback to pixels if JPEG reconstruction fails
+    ///
+    /// # Note
+    /// You can reconstruct JPEG data or get pixels in one go
+    ///
+    /// # Errors
+    /// Return a [`DecodeError`] when internal decoder fails
+    pub fn reconstruct(&self, data: &[u8]) -> Result<(Metadata, Data), DecodeError> {
+        self.reconstruct_input(SliceInput::new(data))
+    }
+
+    /// Decode a JPEG XL image from a reader.
+    /// Input is read on demand, so the whole file is never buffered in memory.
+    ///
+    /// # Note
+    /// The reader is not buffered internally, wrap it in a [`BufReader`](std::io::BufReader)
+    /// if small reads are expensive
+    ///
+    /// # Errors
+    /// Return a [`DecodeError`] when internal decoder fails or reading fails
+    pub fn decode_reader<R: Read>(&self, reader: R) -> Result<(Metadata, Pixels), DecodeError> {
+        self.decode_input(ReaderInput::new(reader))
+    }
+
+    /// Decode a JPEG XL image from a reader to a specific pixel type.
+    /// See [`decode_reader`](Self::decode_reader) for details.
+    ///
+    /// # Errors
+    /// Return a [`DecodeError`] when internal decoder fails or reading fails
+    pub fn decode_reader_with<T: PixelType, R: Read>(
+        &self,
+        reader: R,
+    ) -> Result<(Metadata, Vec<T>), DecodeError> {
+        self.decode_input_with(ReaderInput::new(reader))
+    }
+
+    /// Decode a JPEG XL image from a reader directly into `buffer`.
+    /// See [`decode_into`](Self::decode_into) and [`decode_reader`](Self::decode_reader) for
+    /// details.
+    ///
+    /// # Errors
+    /// Return a [`DecodeError`] when the buffer is invalid, internal decoder fails or
+    /// reading fails
+    pub fn decode_reader_into<T: PixelType, R: Read>(
+        &self,
+        reader: R,
+        buffer: &mut [T],
+        stride: Option<usize>,
+    ) -> Result<Metadata, DecodeError> {
+        self.decode_input_into(ReaderInput::new(reader), buffer, stride)
+    }
+
+    /// Reconstruct JPEG data from a reader. Fallback to pixels if JPEG reconstruction fails.
+    /// See [`decode_reader`](Self::decode_reader) for details.
+    ///
+    /// # Errors
+    /// Return a [`DecodeError`] when internal decoder fails or reading fails
+    pub fn reconstruct_reader<R: Read>(&self, reader: R) -> Result<(Metadata, Data), DecodeError> {
+        self.reconstruct_input(ReaderInput::new(reader))
+    }
+}
+
+impl<'pr, 'mm, 'cms> JxlDecoder<'pr, 'mm, 'cms> {
+    /// Start decoding input which arrives in chunks.
+    /// See [`StreamingDecoder`] for details.
+    ///
+    /// # Errors
+    /// Return a [`DecodeError`] when internal decoder fails
+    pub fn stream(&mut self) -> Result<StreamingDecoder<'_, 'pr, 'mm, 'cms>, DecodeError> {
+        let state = DecodeState::new(None, self.icc_profile, false);
+        StreamingDecoder::new(self, state)
+    }
+
+    /// Start decoding input which arrives in chunks, to a specific pixel type.
+    /// See [`StreamingDecoder`] for details.
+    ///
+    /// # Errors
+    /// Return a [`DecodeError`] when internal decoder fails
+    pub fn stream_with<T: PixelType>(
+        &mut self,
+    ) -> Result<StreamingDecoder<'_, 'pr, 'mm, 'cms>, DecodeError> {
+        let state = DecodeState::new(Some(T::pixel_type()), self.icc_profile, false);
+        StreamingDecoder::new(self, state)
+    }
+
+    /// Iterate over the frames of an animation.
+    /// See [`Frames`] for details.
+    ///
+    /// # Errors
+    /// Return a [`DecodeError`] when the signature is invalid or internal decoder fails
+    pub fn frames<'data>(
+        &mut self,
+        data: &'data [u8],
+    ) -> Result<Frames<'_, 'data, 'pr, 'mm, 'cms>, DecodeError> {
+        let state = DecodeState::new(None, self.icc_profile, false);
+        Frames::new(self, data, state)
+    }
+
+    /// Iterate over the layers of a multi-layer image, without blending them.
+    /// Each layer has its own size, and its position and blending are described in
+    /// [`FrameInfo::layer`]. See [`Frames`] for details.
+    ///
+    /// # Note
+    /// Coalescing is disabled regardless of the [`coalescing`](Self::coalescing) option
+    ///
+    /// # Errors
+    /// Return a [`DecodeError`] when the signature is invalid or internal decoder fails
+    pub fn layers<'data>(
+        &mut self,
+        data: &'data [u8],
+    ) -> Result<Frames<'_, 'data, 'pr, 'mm, 'cms>, DecodeError> {
+        let mut state = DecodeState::new(None, self.icc_profile, false);
+        state.coalescing = Some(false);
+        Frames::new(self, data, state)
+    }
+}
+
+/// Size of a sample of the data type in bytes
+fn bytes_per_sample(data_type: JxlDataType) -> usize {
+    match data_type {
+        JxlDataType::Uint8 => 1,
+        JxlDataType::Uint16 | JxlDataType::Float16 => 2,
+        JxlDataType::Float => 4,
+    }
+}
+
+/// Return [`DecodeError::LimitExceeded`] if `actual` is over the `limit`
+fn check_limit(kind: LimitKind, limit: Option<u64>, actual: u64) -> Result<(), DecodeError> {
+    match limit {
+        Some(limit) if actual > limit => Err(DecodeError::LimitExceeded {
+            kind,
+            limit,
+            actual,
+        }),
+        _ => Ok(()),
+    }
 }
 
-impl Drop for JxlDecoder<'_, '_> {
+impl Drop for JxlDecoder<'_, '_, '_> {
     fn drop(&mut self) {
         unsafe { JxlDecoderDestroy(self.dec) };
     }
@@ -529,9 +1280,9 @@ impl Drop for JxlDecoder<'_, '_> {
 //
 // Note: JxlDecoder is NOT Sync because the underlying C API is not safe for
 // concurrent access from multiple threads.
-unsafe impl Send for JxlDecoder<'_, '_> {}
+unsafe impl Send for JxlDecoder<'_, '_, '_> {}
 
 /// Return a [`JxlDecoderBuilder`] with default settings
-pub fn decoder_builder<'prl, 'mm>() -> JxlDecoderBuilder<'prl, 'mm> {
+pub fn decoder_builder<'prl, 'mm, 'cms>() -> JxlDecoderBuilder<'prl, 'mm, 'cms> {
     JxlDecoder::builder()
 }
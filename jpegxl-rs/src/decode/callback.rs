@@ -0,0 +1,232 @@
+/*
+This file is part of jpegxl-rs.
+
+jpegxl-rs is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+jpegxl-rs is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with jpegxl-rs.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::{
+    ffi::c_void,
+    marker::PhantomData,
+    sync::{Mutex, PoisonError},
+};
+
+use jpegxl_sys::{
+    common::types::JxlPixelFormat,
+    decode::{
+        JxlDecoder, JxlDecoderSetImageOutCallback, JxlDecoderSetMultithreadedImageOutCallback,
+        JxlDecoderStatus,
+    },
+};
+
+use crate::common::{CancellationToken, PixelType};
+
+/// Receiver of decoded rows from multiple threads.
+/// See [`JxlDecoder::decode_rows_parallel`](super::JxlDecoder::decode_rows_parallel).
+pub trait RowCallback<T: PixelType>: Sync {
+    /// State of a run, shared by all threads
+    type State: Sync;
+
+    /// Prepare for [`run`](Self::run), which is called from up to `num_threads` threads
+    /// concurrently, with at most `pixels_per_thread` pixels each time
+    fn init(&mut self, num_threads: usize, pixels_per_thread: usize) -> Self::State;
+
+    /// Receive the interleaved samples of part of a row, starting from the pixel at (`x`, `y`).
+    /// `thread_id` is less than `num_threads` given to [`init`](Self::init)
+    fn run(&self, state: &Self::State, thread_id: usize, x: usize, y: usize, pixels: &[T]);
+
+    /// Clean up after all calls to [`run`](Self::run)
+    fn destroy(&mut self, state: Self::State);
+}
+
+/// Callbacks set when the pixel format is known
+#[derive(Clone, Copy)]
+pub(crate) struct ImageOutCallback {
+    opaque: *mut c_void,
+    set: unsafe fn(*mut c_void, *mut JxlDecoder, &JxlPixelFormat) -> JxlDecoderStatus,
+}
+
+impl ImageOutCallback {
+    /// Set the callbacks to the decoder
+    pub(crate) fn set(self, dec: *mut JxlDecoder, format: &JxlPixelFormat) -> JxlDecoderStatus {
+        unsafe { (self.set)(self.opaque, dec, format) }
+    }
+}
+
+/// View the pixels passed by `libjxl` as a slice
+///
+/// # Safety
+/// `pixels` must point to `len` samples of type `T`
+unsafe fn pixels<'a, T>(pixels: *const c_void, len: usize) -> &'a [T] {
+    let pixels = pixels.cast::<T>();
+    // `libjxl` passes aligned rows of native endian samples
+    debug_assert!(pixels.is_aligned());
+    unsafe { std::slice::from_raw_parts(pixels, len) }
+}
+
+/// Rows are no longer passed once cancelled, as `libjxl` cannot be stopped from a callback
+fn is_cancelled(cancel: Option<&CancellationToken>) -> bool {
+    cancel.is_some_and(CancellationToken::is_cancelled)
+}
+
+/// Rows passed to a closure, one call at a time
+pub(crate) struct FnRows<F, T> {
+    callback: Mutex<F>,
+    cancel: Option<CancellationToken>,
+    num_channels: usize,
+    _pixel_type: PhantomData<fn(&[T])>,
+}
+
+impl<F, T> FnRows<F, T>
+where
+    F: FnMut(usize, usize, &[T]) + Send,
+    T: PixelType,
+{
+    pub(crate) fn new(callback: F, cancel: Option<CancellationToken>) -> Self {
+        Self {
+            callback: Mutex::new(callback),
+            cancel,
+            num_channels: 0,
+            _pixel_type: PhantomData,
+        }
+    }
+
+    /// `self` must outlive the decoding
+    pub(crate) fn image_out(&mut self) -> ImageOutCallback {
+        ImageOutCallback {
+            opaque: std::ptr::from_mut(self).cast(),
+            set: Self::set,
+        }
+    }
+
+    unsafe fn set(
+        opaque: *mut c_void,
+        dec: *mut JxlDecoder,
+        format: &JxlPixelFormat,
+    ) -> JxlDecoderStatus {
+        unsafe {
+            (*opaque.cast::<Self>()).num_channels = format.num_channels as usize;
+            JxlDecoderSetImageOutCallback(dec, format, Self::run, opaque)
+        }
+    }
+
+    extern "C" fn run(
+        opaque: *mut c_void,
+        x: usize,
+        y: usize,
+        num_pixels: usize,
+        pixels: *const c_void,
+    ) {
+        let this = unsafe { &*opaque.cast::<Self>() };
+        if is_cancelled(this.cancel.as_ref()) {
+            return;
+        }
+        let pixels = unsafe { self::pixels(pixels, num_pixels * this.num_channels) };
+
+        let mut callback = this.callback.lock().unwrap_or_else(PoisonError::into_inner);
+        callback(x, y, pixels);
+    }
+}
+
+/// Rows passed to a [`RowCallback`] from multiple threads
+pub(crate) struct ParallelRows<C, T> {
+    callback: *mut C,
+    cancel: Option<CancellationToken>,
+    num_channels: usize,
+    _pixel_type: PhantomData<fn(&[T])>,
+}
+
+/// State of a run of [`ParallelRows`]
+struct Worker<C: RowCallback<T>, T: PixelType> {
+    callback: *mut C,
+    cancel: Option<CancellationToken>,
+    num_channels: usize,
+    state: C::State,
+}
+
+impl<C: RowCallback<T>, T: PixelType> ParallelRows<C, T> {
+    /// `callback` must outlive the decoding
+    pub(crate) fn new(callback: &mut C, cancel: Option<CancellationToken>) -> Self {
+        Self {
+            callback,
+            cancel,
+            num_channels: 0,
+            _pixel_type: PhantomData,
+        }
+    }
+
+    /// `self` must outlive the decoding
+    pub(crate) fn image_out(&mut self) -> ImageOutCallback {
+        ImageOutCallback {
+            opaque: std::ptr::from_mut(self).cast(),
+            set: Self::set,
+        }
+    }
+
+    unsafe fn set(
+        opaque: *mut c_void,
+        dec: *mut JxlDecoder,
+        format: &JxlPixelFormat,
+    ) -> JxlDecoderStatus {
+        unsafe {
+            (*opaque.cast::<Self>()).num_channels = format.num_channels as usize;
+            JxlDecoderSetMultithreadedImageOutCallback(
+                dec,
+                format,
+                Self::init,
+                Self::run,
+                Self::destroy,
+                opaque,
+            )
+        }
+    }
+
+    extern "C" fn init(
+        init_opaque: *mut c_void,
+        num_threads: usize,
+        pixels_per_thread: usize,
+    ) -> *mut c_void {
+        let this = unsafe { &*init_opaque.cast::<Self>() };
+        // No run is in progress, so the callback is not shared
+        let state = unsafe { &mut *this.callback }.init(num_threads, pixels_per_thread);
+
+        let worker = Worker {
+            callback: this.callback,
+            cancel: this.cancel.clone(),
+            num_channels: this.num_channels,
+            state,
+        };
+        Box::into_raw(Box::new(worker)).cast()
+    }
+
+    extern "C" fn run(
+        run_opaque: *mut c_void,
+        thread_id: usize,
+        x: usize,
+        y: usize,
+        num_pixels: usize,
+        pixels: *const c_void,
+    ) {
+        let worker = unsafe { &*run_opaque.cast::<Worker<C, T>>() };
+        if is_cancelled(worker.cancel.as_ref()) {
+            return;
+        }
+        let pixels = unsafe { self::pixels(pixels, num_pixels * worker.num_channels) };
+        unsafe { &*worker.callback }.run(&worker.state, thread_id, x, y, pixels);
+    }
+
+    extern "C" fn destroy(run_opaque: *mut c_void) {
+        let worker = unsafe { Box::from_raw(run_opaque.cast::<Worker<C, T>>()) };
+        unsafe { &mut *worker.callback }.destroy(worker.state);
+    }
+}
@@ -0,0 +1,140 @@
+/*
+This file is part of jpegxl-rs.
+
+jpegxl-rs is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+jpegxl-rs is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with jpegxl-rs.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use super::{
+    BasicInfo, DecodeState, Event, FrameInfo, JxlDecoder, Pixels, SliceInput, StreamingDecoder,
+};
+use crate::{errors::DecodeError, utils::check_valid_signature};
+
+/// Iterator over the frames of an image, returned by [`JxlDecoder::frames`] and
+/// [`JxlDecoder::layers`]
+///
+/// Each item is the information and the pixels of a frame. With coalescing enabled (the default),
+/// every frame has the image dimensions and zero-duration frames are merged, which is what an
+/// animation player needs. Otherwise each layer is returned separately, with its own size.
+/// The iterator stops after the first error.
+///
+/// Frames are skipped without being decoded by [`nth`](Iterator::nth), and therefore by
+/// [`step_by`](Iterator::step_by) too. [`rewind`](Self::rewind) and [`frame`](Self::frame)
+/// restart from the first frame without re-creating the decoder.
+///
+/// # Example
+/// ```
+/// # || -> Result<(), Box<dyn std::error::Error>> {
+/// use jpegxl_rs::decoder_builder;
+///
+/// let sample = include_bytes!("../../../samples/sample.jxl");
+/// let mut decoder = decoder_builder().build()?;
+///
+/// for frame in decoder.frames(sample)? {
+///     let (info, _pixels) = frame?;
+///     println!("{} ms", info.duration_ms);
+/// }
+/// # Ok(())
+/// # };
+/// ```
+pub struct Frames<'dec, 'data, 'pr, 'mm, 'cms> {
+    stream: StreamingDecoder<'dec, 'pr, 'mm, 'cms>,
+    data: &'data [u8],
+    /// Input which is not given to the decoder yet
+    input: SliceInput<'data>,
+    info: Option<FrameInfo>,
+    done: bool,
+}
+
+impl<'dec, 'data, 'pr, 'mm, 'cms> Frames<'dec, 'data, 'pr, 'mm, 'cms> {
+    pub(crate) fn new(
+        dec: &'dec mut JxlDecoder<'pr, 'mm, 'cms>,
+        data: &'data [u8],
+        state: DecodeState,
+    ) -> Result<Self, DecodeError> {
+        if check_valid_signature(data) != Some(true) {
+            return Err(DecodeError::InvalidInput);
+        }
+
+        Ok(Self {
+            stream: StreamingDecoder::new(dec, state)?,
+            data,
+            input: SliceInput::new(data),
+            info: None,
+            done: false,
+        })
+    }
+
+    /// Basic information, available after the first frame is decoded.
+    /// The animation header in it has the ticks per second and the number of loops
+    #[must_use]
+    pub fn basic_info(&self) -> Option<&BasicInfo> {
+        self.stream.basic_info()
+    }
+
+    /// Restart from the first frame
+    pub fn rewind(&mut self) {
+        self.stream.rewind();
+        self.input = SliceInput::new(self.data);
+        self.info = None;
+        self.done = false;
+    }
+
+    /// Decode the frame at `index`, counting from the first frame regardless of the
+    /// current position. Return `None` if there are not enough frames
+    pub fn frame(&mut self, index: usize) -> Option<Result<(FrameInfo, Pixels), DecodeError>> {
+        self.rewind();
+        self.nth(index)
+    }
+
+    fn next_frame(&mut self) -> Result<Option<(FrameInfo, Pixels)>, DecodeError> {
+        loop {
+            match self.stream.process()? {
+                Event::NeedMoreInput => self.stream.feed_input(&mut self.input)?,
+                Event::Frame(info) => self.info = Some(info),
+                Event::FullImage(pixels) => {
+                    let info = self
+                        .info
+                        .take()
+                        .ok_or(DecodeError::InternalError("Frame info not available"))?;
+                    return Ok(Some((info, pixels)));
+                }
+                Event::Complete(_) => return Ok(None),
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Iterator for Frames<'_, '_, '_, '_, '_> {
+    type Item = Result<(FrameInfo, Pixels), DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let frame = self.next_frame();
+        if !matches!(frame, Ok(Some(_))) {
+            self.done = true;
+        }
+        frame.transpose()
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        if n > 0 && !self.done {
+            self.stream.skip_frames(n);
+        }
+        self.next()
+    }
+}
@@ -0,0 +1,131 @@
+/*
+This file is part of jpegxl-rs.
+
+jpegxl-rs is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+jpegxl-rs is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with jpegxl-rs.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::io::{ErrorKind, Read};
+
+use jpegxl_sys::decode::{
+    JxlDecoder, JxlDecoderCloseInput, JxlDecoderReleaseInput, JxlDecoderSetInput,
+    JxlDecoderSizeHintBasicInfo,
+};
+
+use crate::{
+    errors::{DecodeError, check_dec_status},
+    utils::check_valid_signature,
+};
+
+/// Size of each read after the basic info is available
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Input source of the one-shot decoding functions
+pub(crate) trait Input {
+    /// Give more input to the decoder.
+    /// Return [`DecodeError::GenericError`] if the source is exhausted, since the decoder
+    /// cannot finish with the input given so far
+    fn more(&mut self, dec: *mut JxlDecoder) -> Result<(), DecodeError>;
+}
+
+/// Input from a complete in-memory buffer
+pub(crate) struct SliceInput<'a>(Option<&'a [u8]>);
+
+impl<'a> SliceInput<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self(Some(data))
+    }
+}
+
+impl Input for SliceInput<'_> {
+    fn more(&mut self, dec: *mut JxlDecoder) -> Result<(), DecodeError> {
+        let Some(data) = self.0.take() else {
+            return Err(DecodeError::GenericError);
+        };
+
+        if check_valid_signature(data) != Some(true) {
+            return Err(DecodeError::InvalidInput);
+        }
+
+        check_dec_status(unsafe { JxlDecoderSetInput(dec, data.as_ptr(), data.len()) })?;
+        unsafe { JxlDecoderCloseInput(dec) };
+
+        Ok(())
+    }
+}
+
+/// Input pulled from a reader on demand
+pub(crate) struct ReaderInput<R> {
+    reader: R,
+    /// Input which is not consumed by the decoder yet
+    buffer: Vec<u8>,
+    started: bool,
+    closed: bool,
+}
+
+impl<R: Read> ReaderInput<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buffer: vec![],
+            started: false,
+            closed: false,
+        }
+    }
+}
+
+impl<R: Read> Input for ReaderInput<R> {
+    fn more(&mut self, dec: *mut JxlDecoder) -> Result<(), DecodeError> {
+        if self.closed {
+            return Err(DecodeError::GenericError);
+        }
+
+        let remaining = unsafe { JxlDecoderReleaseInput(dec) };
+        self.buffer.drain(..self.buffer.len() - remaining);
+
+        // Only read as much as needed for the basic info at first
+        let size = if self.started {
+            READ_CHUNK_SIZE
+        } else {
+            unsafe { JxlDecoderSizeHintBasicInfo(dec) }
+        };
+
+        let start = self.buffer.len();
+        self.buffer.resize(start + size, 0);
+        let read = loop {
+            match self.reader.read(&mut self.buffer[start..]) {
+                Ok(n) => break n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        };
+        self.buffer.truncate(start + read);
+
+        if !self.started {
+            if check_valid_signature(&self.buffer) == Some(false) {
+                return Err(DecodeError::InvalidInput);
+            }
+            self.started = true;
+        }
+
+        check_dec_status(unsafe {
+            JxlDecoderSetInput(dec, self.buffer.as_ptr(), self.buffer.len())
+        })?;
+        if read == 0 {
+            unsafe { JxlDecoderCloseInput(dec) };
+            self.closed = true;
+        }
+
+        Ok(())
+    }
+}
@@ -16,10 +16,20 @@ along with jpegxl-rs.  If not, see <https://www.gnu.org/licenses/>.
 */
 
 use half::f16;
-use jpegxl_sys::common::types::{JxlDataType, JxlPixelFormat};
+use jpegxl_sys::{
+    color::color_encoding::{JxlColorEncoding, JxlPrimaries, JxlTransferFunction, JxlWhitePoint},
+    common::types::{JxlDataType, JxlPixelFormat},
+};
 
-use super::Orientation;
-use crate::common::PixelType;
+use super::{
+    AnimationHeader, ColorSpace, ExtraChannelType, LayerInfo, Orientation, PreviewHeader,
+    RenderingIntent,
+};
+use crate::{
+    common::PixelType,
+    errors::DecodeError,
+    gain_map::{GAIN_MAP_BOX, GainMap},
+};
 
 /// Result of decoding
 #[derive(Debug)]
@@ -38,6 +48,8 @@ pub struct Metadata {
     pub num_color_channels: u32,
     /// Whether the image has an alpha channel, from metadata
     pub has_alpha_channel: bool,
+    /// Extra channels, including the alpha channel
+    pub extra_channels: Vec<ExtraChannelInfo>,
     /// Intrinsic width of the image.
     /// Applications are advised to resample the decoded image to the intrinsic dimensions
     pub intrinsic_width: u32,
@@ -46,6 +58,245 @@ pub struct Metadata {
     pub intrinsic_height: u32,
     /// ICC profile
     pub icc_profile: Option<Vec<u8>>,
+    /// Color encoding of the original image, or `None` if it is only described by an ICC profile
+    pub original_color_encoding: Option<EncodedColorProfile>,
+    /// Color encoding of the decoded pixels, or `None` if it is only described by an ICC profile
+    pub data_color_encoding: Option<EncodedColorProfile>,
+    /// Animation header, if the image is animated
+    pub animation: Option<AnimationHeader>,
+    /// Metadata boxes, only collected if `metadata_boxes` is set
+    pub boxes: Vec<MetadataBox>,
+}
+
+impl Metadata {
+    /// Parse the gain map, if the image has one.
+    /// Only available if `metadata_boxes` is set
+    ///
+    /// # Errors
+    /// Return [`DecodeError::InvalidInput`] if the `jhgm` box is invalid
+    pub fn gain_map(&self) -> Result<Option<GainMap>, DecodeError> {
+        self.boxes
+            .iter()
+            .find_map(|b| match b {
+                MetadataBox::Custom(t, data) if *t == GAIN_MAP_BOX => Some(data),
+                _ => None,
+            })
+            .map(|data| GainMap::from_bytes(data))
+            .transpose()
+    }
+}
+
+/// Structured color encoding
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EncodedColorProfile {
+    /// Color space
+    pub color_space: ColorSpace,
+    /// White point
+    pub white_point: WhitePoint,
+    /// Primaries, or `None` for grayscale and XYB color spaces
+    pub primaries: Option<Primaries>,
+    /// Transfer function
+    pub transfer_function: TransferFunction,
+    /// Rendering intent
+    pub rendering_intent: RenderingIntent,
+}
+
+/// White point of a color encoding
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WhitePoint {
+    /// CIE Standard Illuminant D65: 0.3127, 0.3290
+    D65,
+    /// CIE Standard Illuminant E (equal-energy): 1/3, 1/3
+    E,
+    /// DCI-P3 from SMPTE RP 431-2: 0.314, 0.351
+    Dci,
+    /// CIE xy values
+    Custom([f64; 2]),
+}
+
+/// RGB primaries of a color encoding
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Primaries {
+    /// As specified in IEC 61966-2-1 sRGB
+    Srgb,
+    /// As specified in Rec. ITU-R BT.2100-1
+    Rec2100,
+    /// As specified in SMPTE RP 431-2
+    P3,
+    /// CIE xy values of the red, green and blue primaries
+    Custom {
+        /// Red primary
+        red: [f64; 2],
+        /// Green primary
+        green: [f64; 2],
+        /// Blue primary
+        blue: [f64; 2],
+    },
+}
+
+/// Transfer function of a color encoding
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TransferFunction {
+    /// As specified in ITU-R BT.709-6
+    Bt709,
+    /// None of the other values describe the transfer function
+    Unknown,
+    /// The gamma exponent is 1
+    Linear,
+    /// As specified in IEC 61966-2-1 sRGB
+    Srgb,
+    /// As specified in SMPTE ST 2084, used for HDR
+    Pq,
+    /// As specified in SMPTE ST 428-1
+    Dci,
+    /// As specified in Rec. ITU-R BT.2100-1, used for HDR
+    Hlg,
+    /// Power law with the gamma value
+    Gamma(f64),
+}
+
+impl From<&JxlColorEncoding> for EncodedColorProfile {
+    fn from(value: &JxlColorEncoding) -> Self {
+        let white_point = match value.white_point {
+            JxlWhitePoint::D65 => WhitePoint::D65,
+            JxlWhitePoint::E => WhitePoint::E,
+            JxlWhitePoint::Dci => WhitePoint::Dci,
+            JxlWhitePoint::Custom => WhitePoint::Custom(value.white_point_xy),
+        };
+
+        let primaries = match value.color_space {
+            ColorSpace::Gray | ColorSpace::Xyb => None,
+            ColorSpace::Rgb | ColorSpace::Unknown => Some(match value.primaries {
+                JxlPrimaries::SRgb => Primaries::Srgb,
+                JxlPrimaries::Rec2100 => Primaries::Rec2100,
+                JxlPrimaries::P3 => Primaries::P3,
+                JxlPrimaries::Custom => Primaries::Custom {
+                    red: value.primaries_red_xy,
+                    green: value.primaries_green_xy,
+                    blue: value.primaries_blue_xy,
+                },
+            }),
+        };
+
+        let transfer_function = match value.transfer_function {
+            JxlTransferFunction::BT709 => TransferFunction::Bt709,
+            JxlTransferFunction::Unknown => TransferFunction::Unknown,
+            JxlTransferFunction::Linear => TransferFunction::Linear,
+            JxlTransferFunction::SRGB => TransferFunction::Srgb,
+            JxlTransferFunction::PQ => TransferFunction::Pq,
+            JxlTransferFunction::DCI => TransferFunction::Dci,
+            JxlTransferFunction::HLG => TransferFunction::Hlg,
+            JxlTransferFunction::Gamma => TransferFunction::Gamma(value.gamma),
+        };
+
+        Self {
+            color_space: value.color_space,
+            white_point,
+            primaries,
+            transfer_function,
+            rendering_intent: value.rendering_intent,
+        }
+    }
+}
+
+/// Header information of an image, read without decoding the pixels
+#[derive(Clone, Debug)]
+pub struct ImageInfo {
+    /// Width of the image
+    pub width: u32,
+    /// Height of the image
+    pub height: u32,
+    /// Original image color channel bit depth
+    pub bits_per_sample: u32,
+    /// Original image color channel floating point exponent bits, or `0` if they are integers
+    pub exponent_bits_per_sample: u32,
+    /// Number of color channels per pixel _without_ alpha channel
+    pub num_color_channels: u32,
+    /// Whether the image has an alpha channel
+    pub has_alpha_channel: bool,
+    /// Orientation
+    pub orientation: Orientation,
+    /// Animation header, if the image is animated
+    pub animation: Option<AnimationHeader>,
+    /// Preview header, if the image has a preview
+    pub preview: Option<PreviewHeader>,
+    /// Extra channels, including the alpha channel
+    pub extra_channels: Vec<ExtraChannelInfo>,
+    /// Color encoding of the image, or `None` if it is only described by an ICC profile
+    pub color_encoding: Option<EncodedColorProfile>,
+    /// Whether the codestream is embedded in the container format
+    pub have_container: bool,
+}
+
+/// Metadata box
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MetadataBox {
+    /// EXIF
+    /// The contents of this box are prepended by a 4-byte tiff header offset
+    Exif(Vec<u8>),
+    /// XMP/IPTC metadata
+    Xmp(Vec<u8>),
+    /// JUMBF superbox
+    Jumb(Vec<u8>),
+    /// Other boxes, except the ones used by the JPEG XL container itself.
+    /// Compressed `brob` boxes are reported as is if decompression is disabled
+    Custom([u8; 4], Vec<u8>),
+}
+
+impl MetadataBox {
+    /// Create a box from its type and contents.
+    /// Return `None` for boxes used by the JPEG XL container itself
+    pub(crate) fn new(box_type: [u8; 4], data: Vec<u8>) -> Option<Self> {
+        match &box_type {
+            b"Exif" => Some(Self::Exif(data)),
+            b"xml " => Some(Self::Xmp(data)),
+            b"jumb" => Some(Self::Jumb(data)),
+            b"ftyp" | b"jbrd" | [b'j' | b'J', b'x' | b'X', b'l' | b'L', _] => None,
+            _ => Some(Self::Custom(box_type, data)),
+        }
+    }
+}
+
+/// Information of an extra channel
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExtraChannelInfo {
+    /// Type of the channel
+    pub channel_type: ExtraChannelType,
+    /// Bits per sample
+    pub bits_per_sample: u32,
+    /// Exponent bits per sample, 0 for integer samples
+    pub exponent_bits_per_sample: u32,
+    /// The channel is downsampled by `1 << dim_shift` in the codestream
+    pub dim_shift: u32,
+    /// Name of the channel, empty if not set
+    pub name: String,
+    /// Whether the alpha channel is premultiplied.
+    /// Only applies to [`ExtraChannelType::Alpha`]
+    pub alpha_premultiplied: bool,
+    /// Spot color in linear RGBA.
+    /// Only applies to [`ExtraChannelType::SpotColor`]
+    pub spot_color: [f32; 4],
+    /// Index of the color filter array channel.
+    /// Only applies to [`ExtraChannelType::Cfa`]
+    pub cfa_channel: u32,
+}
+
+/// Information of a frame
+#[derive(Clone, Debug)]
+pub struct FrameInfo {
+    /// Duration in ticks, see [`AnimationHeader`] for the ticks per second
+    pub duration: u32,
+    /// Duration in milliseconds
+    pub duration_ms: f64,
+    /// SMPTE timecode, 0 if the animation has no timecodes
+    pub timecode: u32,
+    /// Name of the frame, empty if not set
+    pub name: String,
+    /// Whether it is the last frame
+    pub is_last: bool,
+    /// Position, size and blending of the layer.
+    /// With coalescing enabled, the layer always covers the whole image
+    pub layer: LayerInfo,
 }
 
 /// Pixels returned from the decoder
@@ -82,7 +333,10 @@ pub enum Data {
 
 #[cfg(test)]
 mod tests {
+    use jpegxl_sys::common::types::JxlBool;
+
     use super::*;
+    use crate::decode::{BlendInfo, BlendMode};
 
     #[test]
     #[cfg_attr(coverage_nightly, coverage(off))]
@@ -97,12 +351,63 @@ mod tests {
                 orientation: Orientation::Identity,
                 num_color_channels: 0,
                 has_alpha_channel: false,
+                extra_channels: vec![],
                 intrinsic_width: 0,
                 intrinsic_height: 0,
                 icc_profile: None,
+                original_color_encoding: None,
+                data_color_encoding: None,
+                animation: None,
+                boxes: vec![],
+            }
+        );
+
+        println!(
+            "{:?}",
+            FrameInfo {
+                duration: 0,
+                duration_ms: 0.0,
+                timecode: 0,
+                name: String::new(),
+                is_last: false,
+                layer: LayerInfo {
+                    have_crop: JxlBool::False,
+                    crop_x0: 0,
+                    crop_y0: 0,
+                    xsize: 0,
+                    ysize: 0,
+                    blend_info: BlendInfo {
+                        blendmode: BlendMode::Replace,
+                        source: 0,
+                        alpha: 0,
+                        clamp: JxlBool::False,
+                    },
+                    save_as_reference: 0,
+                },
             }
         );
 
         println!("{:?}", Pixels::Float(vec![]));
+        println!("{:?}", MetadataBox::Exif(vec![]));
+    }
+
+    #[test]
+    #[cfg_attr(coverage_nightly, coverage(off))]
+    fn test_box_type() {
+        assert_eq!(
+            MetadataBox::new(*b"Exif", vec![1]),
+            Some(MetadataBox::Exif(vec![1]))
+        );
+        assert_eq!(
+            MetadataBox::new(*b"xml ", vec![1]),
+            Some(MetadataBox::Xmp(vec![1]))
+        );
+        assert_eq!(
+            MetadataBox::new(*b"brob", vec![1]),
+            Some(MetadataBox::Custom(*b"brob", vec![1]))
+        );
+        assert_eq!(MetadataBox::new(*b"jxlc", vec![1]), None);
+        assert_eq!(MetadataBox::new(*b"JXL ", vec![1]), None);
+        assert_eq!(MetadataBox::new(*b"jbrd", vec![1]), None);
     }
 }
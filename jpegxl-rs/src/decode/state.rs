@@ -0,0 +1,531 @@
+/*
+This file is part of jpegxl-rs.
+
+jpegxl-rs is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+jpegxl-rs is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with jpegxl-rs.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::{ffi::c_char, mem::MaybeUninit, ptr::NonNull};
+
+use jpegxl_sys::{
+    common::types::{JxlBool, JxlBoxType, JxlDataType, JxlPixelFormat},
+    decode::{
+        JxlColorProfileTarget, JxlDecoderFlushImage, JxlDecoderGetBasicInfo, JxlDecoderGetBoxType,
+        JxlDecoderGetExtraChannelInfo, JxlDecoderGetExtraChannelName, JxlDecoderGetFrameHeader,
+        JxlDecoderGetFrameName, JxlDecoderProcessInput, JxlDecoderReleaseBoxBuffer,
+        JxlDecoderReleaseJPEGBuffer, JxlDecoderSetBoxBuffer, JxlDecoderSetJPEGBuffer,
+        JxlDecoderStatus,
+    },
+};
+
+use super::{
+    BasicInfo, EncodedColorProfile, ExtraChannelInfo, FrameInfo, ImageInfo, ImageOutCallback,
+    JxlDecoder, Metadata, MetadataBox, check_limit,
+};
+use crate::{
+    common::Endianness,
+    errors::{DecodeError, LimitKind, check_dec_status},
+};
+
+/// Initial size of the buffer for each box
+const BOX_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Destination of the decoded pixels
+#[derive(Clone, Copy)]
+pub(crate) enum ImageOut {
+    /// Allocate `pixels` of the state
+    Pixels,
+    /// Caller-provided buffer, which must outlive the decoding, with rows `stride` bytes apart
+    Buffer {
+        buffer: NonNull<[u8]>,
+        stride: Option<usize>,
+    },
+    /// Pass the pixels to callbacks
+    Callback(ImageOutCallback),
+}
+
+/// Decoding state shared by the one-shot and the streaming decoders
+///
+/// It owns every output buffer handed to `libjxl`, so it must be kept alive until the decoder
+/// is reset.
+pub(crate) struct DecodeState {
+    pub(crate) data_type: Option<JxlDataType>,
+    pub(crate) basic_info: Option<BasicInfo>,
+    pub(crate) icc_profile: Option<Vec<u8>>,
+    pub(crate) original_color_encoding: Option<EncodedColorProfile>,
+    pub(crate) data_color_encoding: Option<EncodedColorProfile>,
+    pub(crate) pixel_format: Option<JxlPixelFormat>,
+    pub(crate) pixels: Vec<u8>,
+    pub(crate) image_out: ImageOut,
+    pub(crate) extra_channel_info: Vec<ExtraChannelInfo>,
+    /// Planes of the extra channels, only output if set
+    pub(crate) extra_channels: Option<Vec<Vec<u8>>>,
+    pub(crate) jpeg_buffer: Option<Vec<u8>>,
+    pub(crate) boxes: Option<Vec<MetadataBox>>,
+    /// Whether `brob` boxes are decompressed, set when the decoder is set up
+    pub(crate) decompress_boxes: bool,
+    /// Type and contents of the box being decoded
+    current_box: Option<([u8; 4], Vec<u8>)>,
+    pub(crate) preview: Option<Vec<u8>>,
+    pub(crate) preview_format: Option<JxlPixelFormat>,
+    /// Information of the current frame, only available when subscribed to frames
+    pub(crate) frame_info: Option<FrameInfo>,
+    /// Override the `coalescing` option of the decoder
+    pub(crate) coalescing: Option<bool>,
+    /// Width of the current layer, only available when subscribed to frames
+    layer_width: Option<u32>,
+    /// Events subscribed in addition to the ones needed by the outputs
+    extra_events: i32,
+    /// Number of frames decoded so far
+    frames: usize,
+    /// Bytes allocated for the outputs so far
+    output_bytes: usize,
+}
+
+impl DecodeState {
+    pub(crate) fn new(
+        data_type: Option<JxlDataType>,
+        with_icc_profile: bool,
+        reconstruct_jpeg: bool,
+    ) -> Self {
+        Self {
+            data_type,
+            basic_info: None,
+            icc_profile: with_icc_profile.then(Vec::new),
+            original_color_encoding: None,
+            data_color_encoding: None,
+            pixel_format: None,
+            pixels: vec![],
+            image_out: ImageOut::Pixels,
+            extra_channel_info: vec![],
+            extra_channels: None,
+            jpeg_buffer: reconstruct_jpeg.then(Vec::new),
+            boxes: None,
+            decompress_boxes: false,
+            current_box: None,
+            preview: None,
+            preview_format: None,
+            frame_info: None,
+            coalescing: None,
+            layer_width: None,
+            extra_events: 0,
+            frames: 0,
+            output_bytes: 0,
+        }
+    }
+
+    /// Events to subscribe to
+    pub(crate) fn events(&self) -> i32 {
+        use JxlDecoderStatus::{
+            BasicInfo, ColorEncoding, FullImage, JPEGReconstruction, PreviewImage,
+        };
+
+        let mut events =
+            BasicInfo as i32 | ColorEncoding as i32 | FullImage as i32 | self.extra_events;
+        if self.jpeg_buffer.is_some() {
+            events |= JPEGReconstruction as i32;
+        }
+        if self.boxes.is_some() {
+            events |= JxlDecoderStatus::Box as i32;
+        }
+        if self.preview.is_some() {
+            events |= PreviewImage as i32;
+        }
+
+        events
+    }
+
+    /// Subscribe to an event, to get it reported by [`step`](Self::step)
+    pub(crate) fn subscribe(&mut self, event: JxlDecoderStatus) {
+        self.extra_events |= event as i32;
+    }
+
+    /// Discard the outputs collected so far, after the decoder is rewound
+    pub(crate) fn rewind(&mut self) {
+        if let Some(boxes) = self.boxes.as_mut() {
+            boxes.clear();
+        }
+        if let Some(buf) = self.jpeg_buffer.as_mut() {
+            buf.clear();
+        }
+        self.current_box = None;
+        self.frame_info = None;
+        self.layer_width = None;
+        self.frames = 0;
+        self.output_bytes = 0;
+    }
+
+    /// Whether the output is a caller-provided buffer with a row stride
+    pub(crate) fn strided_output(&self) -> bool {
+        matches!(
+            self.image_out,
+            ImageOut::Buffer {
+                stride: Some(_),
+                ..
+            }
+        )
+    }
+
+    /// Process input until an informative event occurs
+    ///
+    /// Requests for output buffers are handled internally. Return the status of
+    /// [`JxlDecoderStatus::NeedMoreInput`], [`JxlDecoderStatus::Success`] or any
+    /// subscribed event.
+    pub(crate) fn step(&mut self, dec: &JxlDecoder) -> Result<JxlDecoderStatus, DecodeError> {
+        loop {
+            use JxlDecoderStatus as s;
+
+            dec.check_cancelled()?;
+            let status = unsafe { JxlDecoderProcessInput(dec.dec) };
+
+            match status {
+                s::Error => return Err(DecodeError::GenericError),
+
+                // Get the basic info
+                s::BasicInfo => {
+                    self.read_basic_info(dec)?;
+                    return Ok(status);
+                }
+
+                // Get color encoding
+                s::ColorEncoding => {
+                    dec.set_color_profile()?;
+                    if let Some(icc) = self.icc_profile.as_mut() {
+                        dec.get_icc_profile(icc)?;
+                    }
+                    self.original_color_encoding =
+                        dec.get_color_encoding(JxlColorProfileTarget::Original);
+                    self.data_color_encoding = dec.get_color_encoding(JxlColorProfileTarget::Data);
+                    return Ok(status);
+                }
+
+                // Get the frame header
+                s::Frame => {
+                    let info = self.get_frame_info(dec)?;
+                    self.frames += 1;
+                    check_limit(
+                        LimitKind::Frames,
+                        dec.max_frames.map(|v| v as u64),
+                        self.frames as u64,
+                    )?;
+                    dec.check_size(info.layer.xsize, info.layer.ysize)?;
+                    self.layer_width = Some(info.layer.xsize);
+
+                    // Only subscribed to check the limits otherwise
+                    if self.extra_events & s::Frame as i32 != 0 {
+                        self.frame_info = Some(info);
+                        return Ok(status);
+                    }
+                }
+
+                // Get JPEG reconstruction buffer
+                s::JPEGReconstruction => {
+                    let buf = self.jpeg_buffer.as_mut().ok_or(DecodeError::InternalError(
+                        "JPEG reconstruction without a buffer",
+                    ))?;
+                    dec.reserve_output(&mut self.output_bytes, dec.init_jpeg_buffer)?;
+                    buf.resize(dec.init_jpeg_buffer, 0);
+                    check_dec_status(unsafe {
+                        JxlDecoderSetJPEGBuffer(dec.dec, buf.as_mut_ptr(), buf.len())
+                    })?;
+                }
+
+                // JPEG buffer need more space
+                s::JPEGNeedMoreOutput => {
+                    let buf = self.jpeg_buffer.as_mut().ok_or(DecodeError::InternalError(
+                        "JPEG reconstruction without a buffer",
+                    ))?;
+                    let need_to_write = unsafe { JxlDecoderReleaseJPEGBuffer(dec.dec) };
+
+                    dec.reserve_output(&mut self.output_bytes, need_to_write)?;
+                    buf.resize(buf.len() + need_to_write, 0);
+                    check_dec_status(unsafe {
+                        JxlDecoderSetJPEGBuffer(dec.dec, buf.as_mut_ptr(), buf.len())
+                    })?;
+                }
+
+                // Get the output buffer
+                s::NeedImageOutBuffer => self.set_image_out(dec)?,
+
+                // Start collecting a box
+                s::Box => self.start_box(dec)?,
+
+                // Box buffer need more space
+                s::BoxNeedMoreOutput => self.grow_box(dec)?,
+
+                s::Success => {
+                    self.finish_box(dec);
+
+                    if let Some(buf) = self.jpeg_buffer.as_mut() {
+                        let remaining = unsafe { JxlDecoderReleaseJPEGBuffer(dec.dec) };
+
+                        buf.truncate(buf.len() - remaining);
+                        buf.shrink_to_fit();
+                    }
+
+                    return Ok(status);
+                }
+
+                // Informational events
+                s::NeedMoreInput | s::FullImage | s::FrameProgression | s::PreviewImage => {
+                    return Ok(status);
+                }
+
+                // Get the preview output buffer
+                s::NeedPreviewOutBuffer => {
+                    let info = self
+                        .basic_info
+                        .as_ref()
+                        .ok_or(DecodeError::InternalError("Basic info not available"))?;
+                    let buf = self.preview.as_mut().ok_or(DecodeError::InternalError(
+                        "Preview output without a buffer",
+                    ))?;
+                    let format = dec.pixel_format(info, self.data_type)?;
+                    dec.preview_output(&format, buf, &mut self.output_bytes)?;
+                    self.preview_format = Some(format);
+                }
+
+                // Features not yet implemented in this wrapper
+                s::BoxComplete => return Err(DecodeError::NotImplemented("box complete")),
+            }
+        }
+    }
+
+    /// Read the basic info and the extra channel info, checked against the limits
+    fn read_basic_info(&mut self, dec: &JxlDecoder) -> Result<(), DecodeError> {
+        let mut info = MaybeUninit::uninit();
+        check_dec_status(unsafe { JxlDecoderGetBasicInfo(dec.dec, info.as_mut_ptr()) })?;
+        let info = unsafe { info.assume_init() };
+
+        dec.check_size(info.xsize, info.ysize)?;
+        check_limit(
+            LimitKind::ExtraChannels,
+            dec.max_extra_channels.map(u64::from),
+            info.num_extra_channels.into(),
+        )?;
+
+        if let Some(pr) = dec.parallel_runner {
+            pr.callback_basic_info(&info);
+        }
+        self.extra_channel_info = (0..info.num_extra_channels as usize)
+            .map(|i| get_extra_channel_info(dec, i))
+            .collect::<Result<_, _>>()?;
+        self.basic_info = Some(info);
+
+        Ok(())
+    }
+
+    /// Set the output buffers of the image and the extra channels
+    fn set_image_out(&mut self, dec: &JxlDecoder) -> Result<(), DecodeError> {
+        let info = self
+            .basic_info
+            .as_ref()
+            .ok_or(DecodeError::InternalError("Basic info not available"))?;
+        let mut format = dec.pixel_format(info, self.data_type)?;
+        match self.image_out {
+            ImageOut::Pixels => dec.output(&format, &mut self.pixels, &mut self.output_bytes)?,
+            ImageOut::Buffer { buffer, stride } => {
+                let width = if self.coalescing.or(dec.coalescing).unwrap_or(true) {
+                    Some(info.xsize)
+                } else {
+                    self.layer_width
+                };
+                dec.output_into(width, &mut format, stride, buffer)?;
+            }
+            ImageOut::Callback(callback) => {
+                format.endianness = Endianness::Native;
+                check_dec_status(callback.set(dec.dec, &format))?;
+            }
+        }
+        dec.set_bit_depth(&format)?;
+        self.pixel_format = Some(format);
+
+        if let Some(planes) = self.extra_channels.as_mut() {
+            let format = JxlPixelFormat {
+                num_channels: 1,
+                ..format
+            };
+            planes.resize_with(info.num_extra_channels as usize, Vec::new);
+            for (i, plane) in (0..).zip(planes.iter_mut()) {
+                dec.extra_channel_output(&format, i, plane, &mut self.output_bytes)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn start_box(&mut self, dec: &JxlDecoder) -> Result<(), DecodeError> {
+        self.finish_box(dec);
+
+        let mut box_type = JxlBoxType([0; 4]);
+        check_dec_status(unsafe {
+            JxlDecoderGetBoxType(dec.dec, &mut box_type, self.decompress_boxes.into())
+        })?;
+        let box_type = box_type.0.map(|c| u8::from_ne_bytes(c.to_ne_bytes()));
+
+        // Skip the boxes used by the container itself
+        if MetadataBox::new(box_type, vec![]).is_some() {
+            dec.reserve_output(&mut self.output_bytes, BOX_BUFFER_SIZE)?;
+            let mut buf = vec![0; BOX_BUFFER_SIZE];
+            check_dec_status(unsafe {
+                JxlDecoderSetBoxBuffer(dec.dec, buf.as_mut_ptr(), buf.len())
+            })?;
+            self.current_box = Some((box_type, buf));
+        }
+
+        Ok(())
+    }
+
+    fn grow_box(&mut self, dec: &JxlDecoder) -> Result<(), DecodeError> {
+        let (_, buf) = self
+            .current_box
+            .as_mut()
+            .ok_or(DecodeError::InternalError("Box output without a buffer"))?;
+        let remaining = unsafe { JxlDecoderReleaseBoxBuffer(dec.dec) };
+        let written = buf.len() - remaining;
+
+        dec.reserve_output(&mut self.output_bytes, buf.len())?;
+        buf.resize(buf.len() * 2, 0);
+        check_dec_status(unsafe {
+            JxlDecoderSetBoxBuffer(dec.dec, buf[written..].as_mut_ptr(), buf.len() - written)
+        })
+    }
+
+    /// Store the box being decoded, if any
+    fn finish_box(&mut self, dec: &JxlDecoder) {
+        if let Some((box_type, mut buf)) = self.current_box.take() {
+            let remaining = unsafe { JxlDecoderReleaseBoxBuffer(dec.dec) };
+            buf.truncate(buf.len() - remaining);
+
+            if let (Some(boxes), Some(b)) = (self.boxes.as_mut(), MetadataBox::new(box_type, buf)) {
+                boxes.push(b);
+            }
+        }
+    }
+
+    fn get_frame_info(&self, dec: &JxlDecoder) -> Result<FrameInfo, DecodeError> {
+        let mut header = MaybeUninit::uninit();
+        check_dec_status(unsafe { JxlDecoderGetFrameHeader(dec.dec, header.as_mut_ptr()) })?;
+        let header = unsafe { header.assume_init() };
+
+        // The name is null-terminated
+        let mut name = vec![0u8; header.name_length as usize + 1];
+        check_dec_status(unsafe {
+            JxlDecoderGetFrameName(dec.dec, name.as_mut_ptr().cast::<c_char>(), name.len())
+        })?;
+        name.pop();
+
+        let duration_ms = self
+            .basic_info
+            .as_ref()
+            .filter(|info| info.have_animation == JxlBool::True && info.animation.tps_numerator > 0)
+            .map_or(0.0, |info| {
+                f64::from(header.duration) * 1000.0 * f64::from(info.animation.tps_denominator)
+                    / f64::from(info.animation.tps_numerator)
+            });
+
+        Ok(FrameInfo {
+            duration: header.duration,
+            duration_ms,
+            timecode: header.timecode,
+            name: String::from_utf8_lossy(&name).into_owned(),
+            is_last: header.is_last == JxlBool::True,
+            layer: header.layer_info,
+        })
+    }
+
+    /// Render the current frame decoded so far into the pixel buffer.
+    /// Return `false` if nothing can be rendered yet, which is not an error
+    pub(crate) fn flush(&self, dec: &JxlDecoder) -> bool {
+        self.pixel_format.is_some()
+            && unsafe { JxlDecoderFlushImage(dec.dec) } == JxlDecoderStatus::Success
+    }
+
+    /// Collect the metadata decoded so far
+    pub(crate) fn metadata(&self) -> Result<Metadata, DecodeError> {
+        let info = self
+            .basic_info
+            .as_ref()
+            .ok_or(DecodeError::InternalError("Basic info not available"))?;
+
+        Ok(Metadata {
+            width: info.xsize,
+            height: info.ysize,
+            intensity_target: info.intensity_target,
+            min_nits: info.min_nits,
+            orientation: info.orientation,
+            num_color_channels: info.num_color_channels,
+            has_alpha_channel: info.alpha_bits > 0,
+            extra_channels: self.extra_channel_info.clone(),
+            intrinsic_width: info.intrinsic_xsize,
+            intrinsic_height: info.intrinsic_ysize,
+            icc_profile: self.icc_profile.clone(),
+            original_color_encoding: self.original_color_encoding,
+            data_color_encoding: self.data_color_encoding,
+            animation: (info.have_animation == JxlBool::True).then(|| info.animation.clone()),
+            boxes: self.boxes.clone().unwrap_or_default(),
+        })
+    }
+
+    /// Get the header information, available after the color encoding
+    pub(crate) fn image_info(&self) -> Result<ImageInfo, DecodeError> {
+        let info = self
+            .basic_info
+            .as_ref()
+            .ok_or(DecodeError::InternalError("Basic info not available"))?;
+
+        Ok(ImageInfo {
+            width: info.xsize,
+            height: info.ysize,
+            bits_per_sample: info.bits_per_sample,
+            exponent_bits_per_sample: info.exponent_bits_per_sample,
+            num_color_channels: info.num_color_channels,
+            has_alpha_channel: info.alpha_bits > 0,
+            orientation: info.orientation,
+            animation: (info.have_animation == JxlBool::True).then(|| info.animation.clone()),
+            preview: (info.have_preview == JxlBool::True).then(|| info.preview.clone()),
+            extra_channels: self.extra_channel_info.clone(),
+            color_encoding: self.original_color_encoding,
+            have_container: info.have_container == JxlBool::True,
+        })
+    }
+}
+
+fn get_extra_channel_info(dec: &JxlDecoder, index: usize) -> Result<ExtraChannelInfo, DecodeError> {
+    let mut info = MaybeUninit::uninit();
+    check_dec_status(unsafe { JxlDecoderGetExtraChannelInfo(dec.dec, index, info.as_mut_ptr()) })?;
+    let info = unsafe { info.assume_init() };
+
+    // The name is null-terminated
+    let mut name = vec![0u8; info.name_length as usize + 1];
+    check_dec_status(unsafe {
+        JxlDecoderGetExtraChannelName(
+            dec.dec,
+            index,
+            name.as_mut_ptr().cast::<c_char>(),
+            name.len(),
+        )
+    })?;
+    name.pop();
+
+    Ok(ExtraChannelInfo {
+        channel_type: info.r#type,
+        bits_per_sample: info.bits_per_sample,
+        exponent_bits_per_sample: info.exponent_bits_per_sample,
+        dim_shift: info.dim_shift,
+        name: String::from_utf8_lossy(&name).into_owned(),
+        alpha_premultiplied: info.alpha_premultiplied == JxlBool::True,
+        spot_color: info.spot_color,
+        cfa_channel: info.cfa_channel,
+    })
+}
@@ -0,0 +1,89 @@
+/*
+This file is part of jpegxl-rs.
+
+jpegxl-rs is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+jpegxl-rs is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with jpegxl-rs.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::ptr::NonNull;
+
+use jpegxl_sys::{
+    common::types::JxlDataType,
+    decode::{JxlDecoderReset, JxlDecoderStatus},
+};
+
+use super::{DecodeState, ImageInfo, ImageOut, Input, JxlDecoder, Metadata};
+use crate::errors::DecodeError;
+
+/// Decoding paused after the header, for callers which need the image information before
+/// choosing where the pixels go. The decoder passed to each step must be the same, and it must
+/// not be used for anything else in between.
+pub(crate) struct Steps<I> {
+    state: DecodeState,
+    input: I,
+}
+
+impl<I: Input> Steps<I> {
+    /// Read the header of `input`, with the ICC profile and the metadata boxes before the
+    /// codestream
+    pub(crate) fn header(dec: &JxlDecoder, input: I) -> Result<(Self, ImageInfo), DecodeError> {
+        let mut state = DecodeState::new(None, true, false);
+        state.boxes = Some(vec![]);
+        dec.setup_decoder(&mut state)?;
+
+        let mut steps = Self { state, input };
+        loop {
+            match steps.state.step(dec)? {
+                JxlDecoderStatus::NeedMoreInput => steps.input.more(dec.dec)?,
+                JxlDecoderStatus::ColorEncoding => break,
+                JxlDecoderStatus::Success => {
+                    return Err(DecodeError::InternalError("Color encoding not available"));
+                }
+                _ => {}
+            }
+        }
+
+        let info = steps.state.image_info()?;
+        Ok((steps, info))
+    }
+
+    /// Metadata read so far
+    pub(crate) fn metadata(&self) -> Result<Metadata, DecodeError> {
+        self.state.metadata()
+    }
+
+    /// Decode the rest of the input, writing the pixels of `data_type` to `buffer`
+    pub(crate) fn decode_into(
+        mut self,
+        dec: &JxlDecoder,
+        data_type: JxlDataType,
+        buffer: &mut [u8],
+    ) -> Result<(), DecodeError> {
+        self.state.data_type = Some(data_type);
+        self.state.image_out = ImageOut::Buffer {
+            buffer: NonNull::from(buffer),
+            stride: None,
+        };
+
+        loop {
+            match self.state.step(dec)? {
+                JxlDecoderStatus::NeedMoreInput => self.input.more(dec.dec)?,
+                JxlDecoderStatus::Success => break,
+                _ => {}
+            }
+        }
+        unsafe { JxlDecoderReset(dec.dec) };
+
+        Ok(())
+    }
+}
@@ -0,0 +1,242 @@
+/*
+This file is part of jpegxl-rs.
+
+jpegxl-rs is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+jpegxl-rs is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with jpegxl-rs.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use jpegxl_sys::decode::{
+    JxlDecoderCloseInput, JxlDecoderGetIntendedDownsamplingRatio, JxlDecoderReleaseInput,
+    JxlDecoderReset, JxlDecoderRewind, JxlDecoderSetInput, JxlDecoderSkipCurrentFrame,
+    JxlDecoderSkipFrames, JxlDecoderStatus,
+};
+
+use super::{BasicInfo, DecodeState, FrameInfo, Input, JxlDecoder, Metadata, Pixels, SliceInput};
+use crate::errors::{DecodeError, check_dec_status};
+
+/// Events reported by [`StreamingDecoder::process`]
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Event {
+    /// All input fed so far has been consumed, call [`StreamingDecoder::feed`] to continue
+    NeedMoreInput,
+    /// Basic information, such as image dimensions, is available
+    BasicInfo(BasicInfo),
+    /// Color encoding is available. The ICC profile is retrieved if `icc_profile` is set
+    ColorEncoding(Option<Vec<u8>>),
+    /// Beginning of a frame
+    Frame(FrameInfo),
+    /// A progressive step set by `progressive_detail` is reached,
+    /// call [`StreamingDecoder::flush`] to render the frame decoded so far
+    FrameProgression,
+    /// A frame is fully decoded
+    FullImage(Pixels),
+    /// Decoding is finished
+    Complete(Metadata),
+}
+
+/// Decoder which accepts input in chunks as it arrives
+///
+/// Unconsumed input is kept between calls to [`feed`](Self::feed), so chunks of any size can be
+/// given to the decoder.
+///
+/// # Example
+/// ```
+/// # || -> Result<(), Box<dyn std::error::Error>> {
+/// use jpegxl_rs::{decoder_builder, decode::Event};
+///
+/// let sample = include_bytes!("../../../samples/sample.jxl");
+/// let mut decoder = decoder_builder().build()?;
+/// let mut stream = decoder.stream()?;
+///
+/// let mut chunks = sample.chunks(1024);
+/// loop {
+///     match stream.process()? {
+///         Event::NeedMoreInput => match chunks.next() {
+///             Some(chunk) => stream.feed(chunk)?,
+///             None => stream.close(),
+///         },
+///         Event::BasicInfo(info) => assert!(info.xsize < 4096),
+///         Event::FullImage(pixels) => { /* do something with the pixels */ }
+///         Event::Complete(_) => break,
+///         _ => {}
+///     }
+/// }
+/// # Ok(())
+/// # };
+/// ```
+pub struct StreamingDecoder<'dec, 'pr, 'mm, 'cms> {
+    dec: &'dec mut JxlDecoder<'pr, 'mm, 'cms>,
+    state: DecodeState,
+    /// Input which is not consumed by the decoder yet
+    input: Vec<u8>,
+    closed: bool,
+}
+
+impl<'dec, 'pr, 'mm, 'cms> StreamingDecoder<'dec, 'pr, 'mm, 'cms> {
+    pub(crate) fn new(
+        dec: &'dec mut JxlDecoder<'pr, 'mm, 'cms>,
+        mut state: DecodeState,
+    ) -> Result<Self, DecodeError> {
+        state.boxes = dec.metadata_boxes.then(Vec::new);
+        state.subscribe(JxlDecoderStatus::Frame);
+        state.subscribe(JxlDecoderStatus::FrameProgression);
+        dec.setup_decoder(&mut state)?;
+
+        Ok(Self {
+            dec,
+            state,
+            input: vec![],
+            closed: false,
+        })
+    }
+
+    /// Append a chunk of input
+    ///
+    /// # Errors
+    /// Return [`DecodeError::InputClosed`] if [`close`](Self::close) was called before,
+    /// or a [`DecodeError`] when the internal decoder fails
+    pub fn feed(&mut self, data: &[u8]) -> Result<(), DecodeError> {
+        if self.closed {
+            return Err(DecodeError::InputClosed);
+        }
+
+        let remaining = unsafe { JxlDecoderReleaseInput(self.dec.dec) };
+        self.input.drain(..self.input.len() - remaining);
+        self.input.extend_from_slice(data);
+
+        check_dec_status(unsafe {
+            JxlDecoderSetInput(self.dec.dec, self.input.as_ptr(), self.input.len())
+        })
+    }
+
+    /// Give the rest of the input from `input` without copying it, and mark the end of input.
+    /// The data of `input` must outlive the stream, or the next [`rewind`](Self::rewind)
+    pub(crate) fn feed_input(&mut self, input: &mut SliceInput<'_>) -> Result<(), DecodeError> {
+        if self.closed {
+            return Err(DecodeError::InputClosed);
+        }
+
+        input.more(self.dec.dec)?;
+        self.closed = true;
+        Ok(())
+    }
+
+    /// Mark the end of input
+    ///
+    /// Without it, truncated input is reported as [`Event::NeedMoreInput`] instead of an error
+    pub fn close(&mut self) {
+        if !self.closed {
+            unsafe { JxlDecoderCloseInput(self.dec.dec) };
+            self.closed = true;
+        }
+    }
+
+    /// Process the input fed so far until the next event
+    ///
+    /// # Errors
+    /// Return a [`DecodeError`] when the internal decoder fails
+    pub fn process(&mut self) -> Result<Event, DecodeError> {
+        use JxlDecoderStatus as s;
+
+        Ok(match self.state.step(self.dec)? {
+            s::NeedMoreInput => Event::NeedMoreInput,
+            s::BasicInfo => Event::BasicInfo(
+                self.state
+                    .basic_info
+                    .clone()
+                    .ok_or(DecodeError::InternalError("Basic info not available"))?,
+            ),
+            s::ColorEncoding => Event::ColorEncoding(self.state.icc_profile.clone()),
+            s::Frame => Event::Frame(
+                self.state
+                    .frame_info
+                    .clone()
+                    .ok_or(DecodeError::InternalError("Frame info not available"))?,
+            ),
+            s::FrameProgression => Event::FrameProgression,
+            s::FullImage => {
+                let format = self
+                    .state
+                    .pixel_format
+                    .ok_or(DecodeError::InternalError("Pixel format not available"))?;
+                Event::FullImage(Pixels::new(std::mem::take(&mut self.state.pixels), &format))
+            }
+            s::Success => Event::Complete(self.state.metadata()?),
+            status => return Err(DecodeError::UnknownStatus(status)),
+        })
+    }
+
+    /// Render the current frame decoded so far
+    ///
+    /// Useful after [`Event::FrameProgression`], or after [`Event::NeedMoreInput`] to show a
+    /// partial image while waiting for more input. Return `None` if nothing can be rendered yet.
+    pub fn flush(&mut self) -> Option<Pixels> {
+        let format = self
+            .state
+            .pixel_format
+            .filter(|_| self.state.flush(self.dec))?;
+        Some(Pixels::new(self.state.pixels.clone(), &format))
+    }
+
+    /// Intended downsampling ratio of the frame rendered by [`flush`](Self::flush),
+    /// can be 1, 2, 4 or 8
+    #[must_use]
+    pub fn downsampling_ratio(&self) -> usize {
+        unsafe { JxlDecoderGetIntendedDownsamplingRatio(self.dec.dec) }
+    }
+
+    /// Skip the next `amount` frames, without reporting their events.
+    /// If a frame is being decoded, skipping starts from the next one
+    pub fn skip_frames(&mut self, amount: usize) {
+        unsafe { JxlDecoderSkipFrames(self.dec.dec, amount) };
+    }
+
+    /// Skip the rest of the current frame, after [`Event::Frame`] and before
+    /// [`Event::FullImage`]. Call [`flush`](Self::flush) before if the part decoded so far is
+    /// needed
+    ///
+    /// # Errors
+    /// Return a [`DecodeError`] if no frame is being decoded
+    pub fn skip_current_frame(&mut self) -> Result<(), DecodeError> {
+        check_dec_status(unsafe { JxlDecoderSkipCurrentFrame(self.dec.dec) })
+    }
+
+    /// Restart decoding from the beginning, keeping the settings.
+    /// The input must be fed again from the start, and the events are reported again.
+    ///
+    /// The decoder keeps what it learned about the image, so [`skip_frames`](Self::skip_frames)
+    /// after rewinding is faster than decoding again.
+    pub fn rewind(&mut self) {
+        unsafe {
+            JxlDecoderReleaseInput(self.dec.dec);
+            JxlDecoderRewind(self.dec.dec);
+        }
+        self.input.clear();
+        self.closed = false;
+        self.state.rewind();
+    }
+
+    /// Basic information, if it is already decoded
+    #[must_use]
+    pub fn basic_info(&self) -> Option<&BasicInfo> {
+        self.state.basic_info.as_ref()
+    }
+}
+
+impl Drop for StreamingDecoder<'_, '_, '_, '_> {
+    fn drop(&mut self) {
+        // Release the buffers owned by this stream
+        unsafe { JxlDecoderReset(self.dec.dec) };
+    }
+}
@@ -17,14 +17,20 @@ along with jpegxl-rs.  If not, see <https://www.gnu.org/licenses/>.
 
 //! Encoder of JPEG XL format
 
-use std::{marker::PhantomData, mem::MaybeUninit, ops::Deref, ptr::null};
+use std::{ffi::CString, marker::PhantomData, mem::MaybeUninit, ops::Deref, ptr::null};
 
 use bon::bon;
+use jpegxl_sys::common::types::JxlPixelFormat;
 #[allow(clippy::wildcard_imports)]
 use jpegxl_sys::encoder::encode::*;
 
 use crate::{
-    common::PixelType, errors::EncodeError, memory::MemoryManager, parallel::ParallelRunner,
+    cms::{self, Cms},
+    common::{CancellationToken, PixelType},
+    errors::EncodeError,
+    gain_map::{GAIN_MAP_BOX, GainMap},
+    memory::MemoryManager,
+    parallel::ParallelRunner,
 };
 
 mod options;
@@ -36,6 +42,17 @@ pub use metadata::*;
 mod frame;
 pub use frame::*;
 
+mod extra_channel;
+pub use extra_channel::*;
+
+mod chunked;
+use chunked::ChunkedInput;
+pub use chunked::{ChunkedSource, Rect};
+
+mod output;
+pub use output::{EncoderOutput, SeekOutput, WriteOutput};
+use output::{OutputBuffer, StreamOutput};
+
 // MARK: Utility types
 
 /// Encoder result
@@ -57,7 +74,7 @@ impl<U: PixelType> Deref for EncoderResult<U> {
 
 /// JPEG XL Encoder
 #[allow(clippy::struct_excessive_bools)]
-pub struct JxlEncoder<'prl, 'mm> {
+pub struct JxlEncoder<'prl, 'mm, 'cms> {
     /// Opaque pointer to the underlying encoder
     enc: *mut jpegxl_sys::encoder::encode::JxlEncoder,
     /// Opaque pointer to the encoder options
@@ -67,6 +84,11 @@ pub struct JxlEncoder<'prl, 'mm> {
     ///
     /// Default: false
     pub has_alpha: bool,
+    /// Set extra channels other than the alpha channel, following it if `has_alpha` is set.
+    /// See [`ExtraChannel`]
+    ///
+    /// Default: empty
+    pub extra_channels: Vec<ExtraChannel>,
     /// Set lossless
     ///
     /// Default: false
@@ -103,6 +125,10 @@ pub struct JxlEncoder<'prl, 'mm> {
     ///
     /// Minimum is 0 (highest quality), and maximum is 4 (lowest quality). Default is 0.
     pub decoding_speed: i64,
+    /// Set the buffering strategy, see [`Buffering`]
+    ///
+    /// Default: [`Buffering::Auto`]
+    pub buffering: Buffering,
     /// Set initial output buffer size in bytes.
     /// Anything less than 32 bytes will be rounded up to 32 bytes.
     ///
@@ -114,6 +140,18 @@ pub struct JxlEncoder<'prl, 'mm> {
     /// Default: sRGB for int, Linear sRGB for float
     pub color_encoding: Option<ColorEncoding>,
 
+    /// Set ICC profile of the input pixels, which takes precedence over `color_encoding`.
+    /// For grayscale images, `color_encoding` still needs to be a luma encoding
+    ///
+    /// Default: `None`
+    pub icc_profile: Option<Vec<u8>>,
+
+    /// Set animation settings, making the frames added by [`multiple`](Self::multiple)
+    /// play one after another for their [`duration`](EncoderFrame::duration)
+    ///
+    /// Default: `None`, the frames are layers of a still image
+    pub animation: Option<Animation>,
+
     /// Set HDR target intensity.
     /// Specify the target intensity in nits for 1.0 value
     pub target_intensity: Option<f32>,
@@ -123,6 +161,17 @@ pub struct JxlEncoder<'prl, 'mm> {
     /// Default: `None`, indicating single thread execution
     pub parallel_runner: Option<&'prl dyn ParallelRunner>,
 
+    /// Set color management system, used when the input needs color conversion
+    ///
+    /// Default: `None`, indicating the color management system of `libjxl`
+    pub cms: Option<&'cms dyn Cms>,
+
+    /// Token to cancel the encoding, checked before adding each frame and whenever the encoder
+    /// writes output
+    ///
+    /// Default: `None`
+    pub cancel: Option<CancellationToken>,
+
     /// Whether box is used in encoder
     use_box: bool,
 
@@ -132,7 +181,7 @@ pub struct JxlEncoder<'prl, 'mm> {
 }
 
 #[bon]
-impl<'prl, 'mm> JxlEncoder<'prl, 'mm> {
+impl<'prl, 'mm, 'cms> JxlEncoder<'prl, 'mm, 'cms> {
     /// Build a [`JxlEncoder`]
     ///
     /// # Errors
@@ -141,16 +190,22 @@ impl<'prl, 'mm> JxlEncoder<'prl, 'mm> {
     pub fn new(
         memory_manager: Option<&'mm dyn MemoryManager>,
         #[builder(default)] has_alpha: bool,
+        #[builder(default)] extra_channels: Vec<ExtraChannel>,
         lossless: Option<bool>,
         #[builder(default)] speed: EncoderSpeed,
         #[builder(default = 1.0)] quality: f32,
         #[builder(default)] use_container: bool,
         #[builder(default)] uses_original_profile: bool,
         #[builder(default)] decoding_speed: i64,
+        #[builder(default)] buffering: Buffering,
         init_buffer_size: Option<usize>,
         color_encoding: Option<ColorEncoding>,
+        icc_profile: Option<Vec<u8>>,
+        animation: Option<Animation>,
         target_intensity: Option<f32>,
         parallel_runner: Option<&'prl dyn ParallelRunner>,
+        cms: Option<&'cms dyn Cms>,
+        cancel: Option<CancellationToken>,
         #[builder(default)] use_box: bool,
     ) -> Result<Self, EncodeError> {
         let enc = unsafe {
@@ -170,16 +225,22 @@ impl<'prl, 'mm> JxlEncoder<'prl, 'mm> {
             enc,
             options_ptr,
             has_alpha,
+            extra_channels,
             lossless,
             speed,
             quality,
             use_container,
             uses_original_profile,
             decoding_speed,
+            buffering,
             init_buffer_size: init_buffer_size.map_or(512 * 1024, |v| if v < 32 { 32 } else { v }),
             color_encoding,
+            icc_profile,
+            animation,
             target_intensity,
             parallel_runner,
+            cms,
+            cancel,
             use_box,
             memory_manager,
         })
@@ -188,11 +249,11 @@ impl<'prl, 'mm> JxlEncoder<'prl, 'mm> {
 
 use jxl_encoder_builder::{IsUnset, SetQuality, State};
 
-impl<'prl, 'mm, S: State> JxlEncoderBuilder<'prl, 'mm, S> {
+impl<'prl, 'mm, 'cms, S: State> JxlEncoderBuilder<'prl, 'mm, 'cms, S> {
     /// Set the `quality` parameter from a JPEG-style quality factor (0-100, higher is better
     /// quality).
     #[allow(dead_code)]
-    pub fn jpeg_quality(self, quality: f32) -> JxlEncoderBuilder<'prl, 'mm, SetQuality<S>>
+    pub fn jpeg_quality(self, quality: f32) -> JxlEncoderBuilder<'prl, 'mm, 'cms, SetQuality<S>>
     where
         S::Quality: IsUnset,
     {
@@ -202,7 +263,7 @@ impl<'prl, 'mm, S: State> JxlEncoderBuilder<'prl, 'mm, S> {
 }
 
 // MARK: Private helper functions
-impl JxlEncoder<'_, '_> {
+impl JxlEncoder<'_, '_, '_> {
     /// Error mapping from underlying C const to [`EncodeError`] enum
     #[track_caller]
     #[cfg_attr(coverage_nightly, coverage(off))]
@@ -222,6 +283,19 @@ impl JxlEncoder<'_, '_> {
         }
     }
 
+    /// Return [`EncodeError::Cancelled`] if the encoding is cancelled
+    fn check_cancelled(&self) -> Result<(), EncodeError> {
+        if self
+            .cancel
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+        {
+            Err(EncodeError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
     // Set options
     fn set_options(&self) -> Result<(), EncodeError> {
         self.check_enc_status(unsafe {
@@ -249,6 +323,13 @@ impl JxlEncoder<'_, '_> {
                 self.decoding_speed,
             )
         })?;
+        self.check_enc_status(unsafe {
+            JxlEncoderFrameSettingsSetOption(
+                self.options_ptr,
+                JxlEncoderFrameSettingId::Buffering,
+                self.buffering as _,
+            )
+        })?;
 
         Ok(())
     }
@@ -271,6 +352,10 @@ impl JxlEncoder<'_, '_> {
             }
         }
 
+        if let Some(cms) = &self.cms {
+            unsafe { JxlEncoderSetCms(self.enc, cms::interface(cms)) };
+        }
+
         self.set_options()?;
 
         let mut basic_info = unsafe {
@@ -296,6 +381,8 @@ impl JxlEncoder<'_, '_> {
             basic_info.alpha_bits = 0;
             basic_info.alpha_exponent_bits = 0;
         }
+        basic_info.num_extra_channels +=
+            u32::try_from(self.extra_channels.len()).map_err(|_| EncodeError::BadInput)?;
 
         if let Some(ColorEncoding::SrgbLuma | ColorEncoding::LinearSrgbLuma) = self.color_encoding {
             basic_info.num_color_channels = 1;
@@ -305,22 +392,115 @@ impl JxlEncoder<'_, '_> {
             basic_info.intensity_target = target_intensity;
         }
 
+        if let Some(animation) = &self.animation {
+            basic_info.have_animation = true.into();
+            basic_info.animation = animation.into();
+        }
+
         if let Some(pr) = self.parallel_runner {
             pr.callback_basic_info(&basic_info);
         }
 
         self.check_enc_status(unsafe { JxlEncoderSetBasicInfo(self.enc, &raw const basic_info) })?;
 
-        if let Some(color_encoding) = &self.color_encoding {
+        if let Some(icc) = &self.icc_profile {
+            self.check_enc_status(unsafe {
+                JxlEncoderSetICCProfile(self.enc, icc.as_ptr(), icc.len())
+            })?;
+        } else if let Some(color_encoding) = &self.color_encoding {
             self.check_enc_status(unsafe {
                 JxlEncoderSetColorEncoding(self.enc, &color_encoding.into())
             })?;
         }
+
+        self.set_extra_channels((bits, exp), has_alpha.into())
+    }
+
+    // Set the frame header, which the frame settings keep for the following frames
+    fn set_frame_header<T: PixelType>(&self, frame: &EncoderFrame<T>) -> Result<(), EncodeError> {
+        if self.animation.is_some() {
+            let mut header = unsafe {
+                let mut header = MaybeUninit::uninit();
+                JxlEncoderInitFrameHeader(header.as_mut_ptr());
+                header.assume_init()
+            };
+            header.duration = frame.duration;
+            header.timecode = frame.timecode;
+            self.check_enc_status(unsafe {
+                JxlEncoderSetFrameHeader(self.options_ptr, &raw const header)
+            })?;
+        }
+
+        match frame.name {
+            Some(name) => self.set_frame_name(name),
+            None => Ok(()),
+        }
+    }
+
+    fn set_frame_name(&self, name: &str) -> Result<(), EncodeError> {
+        let name = CString::new(name).map_err(|_| EncodeError::BadInput)?;
+        self.check_enc_status(unsafe {
+            JxlEncoderSetFrameName(self.options_ptr, name.as_ptr().cast())
+        })
+    }
+
+    // Set the information of the extra channels following the alpha channel
+    fn set_extra_channels(
+        &self,
+        (bits, exp): (u32, u32),
+        offset: usize,
+    ) -> Result<(), EncodeError> {
+        for (i, channel) in self.extra_channels.iter().enumerate() {
+            let index = offset + i;
+
+            let mut info = unsafe {
+                let mut info = MaybeUninit::uninit();
+                JxlEncoderInitExtraChannelInfo(channel.channel_type, info.as_mut_ptr());
+                info.assume_init()
+            };
+            (info.bits_per_sample, info.exponent_bits_per_sample) =
+                channel.bits_per_sample.unwrap_or((bits, exp));
+            info.dim_shift = channel.dim_shift;
+            info.alpha_premultiplied = channel.alpha_premultiplied.into();
+            info.spot_color = channel.spot_color;
+            info.cfa_channel = channel.cfa_channel;
+
+            self.check_enc_status(unsafe {
+                JxlEncoderSetExtraChannelInfo(self.enc, index, &raw const info)
+            })?;
+            if !channel.name.is_empty() {
+                self.check_enc_status(unsafe {
+                    JxlEncoderSetExtraChannelName(
+                        self.enc,
+                        index,
+                        channel.name.as_ptr(),
+                        channel.name.len(),
+                    )
+                })?;
+            }
+            if let Some(distance) = channel.distance {
+                self.check_enc_status(unsafe {
+                    JxlEncoderSetExtraChannelDistance(self.options_ptr, index, distance)
+                })?;
+            }
+            if let Some(blend_info) = &channel.blend_info {
+                self.check_enc_status(unsafe {
+                    JxlEncoderSetExtraChannelBlendInfo(self.options_ptr, index, blend_info)
+                })?;
+            }
+        }
+
         Ok(())
     }
 
     // Add a frame
     fn add_frame<T: PixelType>(&self, frame: &EncoderFrame<T>) -> Result<(), EncodeError> {
+        self.check_cancelled()?;
+        if frame.extra_channels.len() != self.extra_channels.len() {
+            return Err(EncodeError::BadInput);
+        }
+        self.set_frame_header(frame)?;
+
         self.check_enc_status(unsafe {
             JxlEncoderAddImageFrame(
                 self.options_ptr,
@@ -328,11 +508,34 @@ impl JxlEncoder<'_, '_> {
                 frame.data.as_ptr().cast(),
                 std::mem::size_of_val(frame.data),
             )
-        })
+        })?;
+        // The name is kept in the frame settings, so it is cleared for the next frames
+        if frame.name.is_some() {
+            self.set_frame_name("")?;
+        }
+
+        let pixel_format = JxlPixelFormat {
+            num_channels: 1,
+            ..frame.pixel_format()
+        };
+        for (index, data) in (u32::from(self.has_alpha)..).zip(&frame.extra_channels) {
+            self.check_enc_status(unsafe {
+                JxlEncoderSetExtraChannelBuffer(
+                    self.options_ptr,
+                    &raw const pixel_format,
+                    data.as_ptr().cast(),
+                    std::mem::size_of_val(*data),
+                    index,
+                )
+            })?;
+        }
+
+        Ok(())
     }
 
     // Add a frame from JPEG raw data
     fn add_jpeg_frame(&self, data: &[u8]) -> Result<(), EncodeError> {
+        self.check_cancelled()?;
         self.check_enc_status(unsafe {
             JxlEncoderAddJPEGFrame(
                 self.options_ptr,
@@ -342,52 +545,96 @@ impl JxlEncoder<'_, '_> {
         })
     }
 
-    fn internal(&mut self) -> Result<Vec<u8>, EncodeError> {
-        unsafe { JxlEncoderCloseInput(self.enc) };
+    // Setup the encoder for JPEG reconstruction
+    fn setup_jpeg(&mut self, data: &[u8]) -> Result<(), EncodeError> {
+        if let Some(runner) = self.parallel_runner {
+            unsafe {
+                self.check_enc_status(JxlEncoderSetParallelRunner(
+                    self.enc,
+                    runner.runner(),
+                    runner.as_opaque_ptr(),
+                ))?;
+            }
+        }
 
-        let mut buffer = vec![0; self.init_buffer_size];
-        let mut next_out = buffer.as_mut_ptr().cast();
-        let mut avail_out = buffer.len();
+        self.set_options()?;
 
-        let mut status;
-        loop {
-            status =
-                unsafe { JxlEncoderProcessOutput(self.enc, &raw mut next_out, &raw mut avail_out) };
+        // If using container format, store JPEG reconstruction metadata
+        self.check_enc_status(unsafe { JxlEncoderStoreJPEGMetadata(self.enc, true.into()) })?;
 
-            if status != JxlEncoderStatus::NeedMoreOutput {
-                break;
-            }
+        self.add_jpeg_frame(data)
+    }
 
-            unsafe {
-                let offset = next_out.offset_from(buffer.as_ptr());
-                debug_assert!(offset >= 0);
+    // Add a frame from a chunked source, which is read immediately
+    fn add_chunked_frame<S: ChunkedSource>(
+        &self,
+        input: &ChunkedInput<S>,
+    ) -> Result<(), EncodeError> {
+        self.check_cancelled()?;
+        self.check_enc_status(unsafe {
+            JxlEncoderAddChunkedFrame(self.options_ptr, true.into(), input.source())
+        })?;
+        input.check()
+    }
 
-                buffer.resize(buffer.len() * 2, 0);
-                next_out = buffer.as_mut_ptr().offset(offset);
-                avail_out = buffer.len().wrapping_add_signed(-offset);
-            }
-        }
-        buffer.truncate(next_out as usize - buffer.as_ptr() as usize);
-        self.check_enc_status(status)?;
+    // Flush everything to the output processor, and reset the encoder for the next image.
+    // Frames which are read while flushing are added by `add_frames`
+    fn flush(
+        &mut self,
+        processor: JxlEncoderOutputProcessor,
+        add_frames: impl FnOnce(&Self) -> Result<(), EncodeError>,
+    ) -> Result<(), EncodeError> {
+        let status = self
+            .check_enc_status(unsafe { JxlEncoderSetOutputProcessor(self.enc, processor) })
+            .and_then(|()| add_frames(self))
+            .and_then(|()| {
+                unsafe { JxlEncoderCloseInput(self.enc) };
+                self.check_enc_status(unsafe { JxlEncoderFlushInput(self.enc) })
+            });
 
         unsafe { JxlEncoderReset(self.enc) };
         self.options_ptr = unsafe { JxlEncoderFrameSettingsCreate(self.enc, null()) };
 
-        buffer.shrink_to_fit();
-        Ok(buffer)
+        status
+    }
+
+    fn internal(
+        &mut self,
+        add_frames: impl FnOnce(&Self) -> Result<(), EncodeError>,
+    ) -> Result<Vec<u8>, EncodeError> {
+        let mut output = OutputBuffer::new(self.init_buffer_size, self.cancel.clone());
+        let status = self.flush(output.processor(), add_frames);
+
+        if output.cancelled() {
+            return Err(EncodeError::Cancelled);
+        }
+        status?;
+
+        Ok(output.into_inner())
+    }
+
+    // Encode to a user provided output
+    pub(crate) fn internal_to(
+        &mut self,
+        output: &mut dyn EncoderOutput,
+        add_frames: impl FnOnce(&Self) -> Result<(), EncodeError>,
+    ) -> Result<(), EncodeError> {
+        let mut stream = StreamOutput::new(output, self.cancel.clone());
+        let status = self.flush(stream.processor(), add_frames);
+        stream.finish(status)
     }
 
     // Start encoding
     fn start_encoding<U: PixelType>(&mut self) -> Result<EncoderResult<U>, EncodeError> {
         Ok(EncoderResult {
-            data: self.internal()?,
+            data: self.internal(|_| Ok(()))?,
             _pixel_type: PhantomData,
         })
     }
 }
 
 // MARK: Public interface
-impl<'prl, 'mm> JxlEncoder<'prl, 'mm> {
+impl<'prl, 'mm, 'cms> JxlEncoder<'prl, 'mm, 'cms> {
     /// Set a specific encoder frame setting
     ///
     /// # Errors
@@ -410,9 +657,9 @@ impl<'prl, 'mm> JxlEncoder<'prl, 'mm> {
         &'enc mut self,
         width: u32,
         height: u32,
-    ) -> Result<MultiFrames<'enc, 'prl, 'mm, U>, EncodeError> {
+    ) -> Result<MultiFrames<'enc, 'prl, 'mm, 'cms, U>, EncodeError> {
         self.setup_encoder(width, height, U::bits_per_sample(), self.has_alpha)?;
-        Ok(MultiFrames::<'enc, 'prl, 'mm, U>(self, PhantomData))
+        Ok(MultiFrames::<'enc, 'prl, 'mm, 'cms, U>(self, PhantomData))
     }
 
     /// Add a metadata box to the encoder
@@ -441,6 +688,15 @@ impl<'prl, 'mm> JxlEncoder<'prl, 'mm> {
         })
     }
 
+    /// Add a gain map to the encoder, stored in a `jhgm` box
+    ///
+    /// # Errors
+    /// Return [`EncodeError`] if it fails to serialize the gain map or to add the box
+    pub fn add_gain_map(&mut self, gain_map: &GainMap) -> Result<(), EncodeError> {
+        let data = gain_map.to_bytes()?;
+        self.add_metadata(&Metadata::Custom(GAIN_MAP_BOX, &data), false)
+    }
+
     /// Encode a JPEG XL image from existing raw JPEG data
     ///
     /// Note: Only support output pixel type of `u8`. Ignore alpha channel settings
@@ -448,25 +704,25 @@ impl<'prl, 'mm> JxlEncoder<'prl, 'mm> {
     /// # Errors
     /// Return [`EncodeError`] if the internal encoder fails to encode
     pub fn encode_jpeg(&mut self, data: &[u8]) -> Result<EncoderResult<u8>, EncodeError> {
-        if let Some(runner) = self.parallel_runner {
-            unsafe {
-                self.check_enc_status(JxlEncoderSetParallelRunner(
-                    self.enc,
-                    runner.runner(),
-                    runner.as_opaque_ptr(),
-                ))?;
-            }
-        }
-
-        self.set_options()?;
-
-        // If using container format, store JPEG reconstruction metadata
-        self.check_enc_status(unsafe { JxlEncoderStoreJPEGMetadata(self.enc, true.into()) })?;
-
-        self.add_jpeg_frame(data)?;
+        self.setup_jpeg(data)?;
         self.start_encoding()
     }
 
+    /// Encode a JPEG XL image from existing raw JPEG data, writing to `output`
+    /// as the data is produced. See [`encode_jpeg`](Self::encode_jpeg)
+    ///
+    /// # Errors
+    /// Return [`EncodeError`] if the internal encoder fails to encode, or
+    /// [`EncodeError::Io`] if it fails to write
+    pub fn encode_jpeg_to(
+        &mut self,
+        data: &[u8],
+        mut output: impl EncoderOutput,
+    ) -> Result<(), EncodeError> {
+        self.setup_jpeg(data)?;
+        self.internal_to(&mut output, |_| Ok(()))
+    }
+
     /// Encode a JPEG XL image from pixels
     ///
     /// Note: Use RGB(3) channels, native endianness and no alignment.
@@ -500,9 +756,112 @@ impl<'prl, 'mm> JxlEncoder<'prl, 'mm> {
         self.add_frame(frame)?;
         self.start_encoding::<U>()
     }
+
+    /// Encode a JPEG XL image from a frame, writing to `output` as the data is produced
+    /// instead of collecting it in memory. See [`encode_frame`](Self::encode_frame)
+    ///
+    /// # Example
+    /// ```
+    /// # || -> Result<(), Box<dyn std::error::Error>> {
+    /// use jpegxl_rs::encode::{EncoderFrame, SeekOutput};
+    /// use jpegxl_rs::encoder_builder;
+    ///
+    /// let pixels = vec![0u8; 64 * 64 * 3];
+    /// let mut encoder = encoder_builder().build()?;
+    /// let file = std::fs::File::create(std::env::temp_dir().join("sample.jxl"))?;
+    /// encoder.encode_frame_to::<u8, u8>(&EncoderFrame::new(&pixels), 64, 64, SeekOutput::new(file)?)?;
+    /// # Ok(())
+    /// # };
+    /// ```
+    ///
+    /// # Errors
+    /// Return [`EncodeError`] if the internal encoder fails to encode, or
+    /// [`EncodeError::Io`] if it fails to write
+    pub fn encode_frame_to<T: PixelType, U: PixelType>(
+        &mut self,
+        frame: &EncoderFrame<T>,
+        width: u32,
+        height: u32,
+        mut output: impl EncoderOutput,
+    ) -> Result<(), EncodeError> {
+        self.setup_encoder(width, height, U::bits_per_sample(), self.has_alpha)?;
+        self.add_frame(frame)?;
+        self.internal_to(&mut output, |_| Ok(()))
+    }
+
+    /// Encode a JPEG XL image from a source handing out rectangles of pixels on demand,
+    /// instead of the whole image at once.
+    /// See [`ChunkedSource`] for the layout of the pixels
+    ///
+    /// # Errors
+    /// Return [`EncodeError`] if the internal encoder fails to encode, or
+    /// [`EncodeError::BadInput`] if the source gives less data than requested
+    pub fn encode_chunked<S: ChunkedSource, U: PixelType>(
+        &mut self,
+        source: S,
+        width: u32,
+        height: u32,
+    ) -> Result<EncoderResult<U>, EncodeError> {
+        self.setup_encoder(width, height, U::bits_per_sample(), self.has_alpha)?;
+        let input = ChunkedInput::new(source);
+        Ok(EncoderResult {
+            data: self.internal(|enc| enc.add_chunked_frame(&input))?,
+            _pixel_type: PhantomData,
+        })
+    }
+
+    /// Encode a JPEG XL image from a source handing out rectangles of pixels on demand,
+    /// writing to `output` as the data is produced.
+    /// With [`Buffering::Stream`], neither the pixels nor the output need to be in memory
+    /// as a whole
+    ///
+    /// # Example
+    /// ```
+    /// # || -> Result<(), Box<dyn std::error::Error>> {
+    /// use jpegxl_rs::encode::{Buffering, ChunkedSource, Rect, WriteOutput};
+    /// use jpegxl_rs::encoder_builder;
+    ///
+    /// /// A horizontal gradient, generated on demand
+    /// struct Gradient;
+    ///
+    /// impl ChunkedSource for Gradient {
+    ///     type Pixel = u8;
+    ///
+    ///     fn color_data(&mut self, rect: Rect) -> Vec<u8> {
+    ///         let row = (rect.x..rect.x + rect.width).flat_map(|x| [(x % 256) as u8; 3]);
+    ///         row.cycle().take(rect.width * rect.height * 3).collect()
+    ///     }
+    ///
+    ///     fn extra_channel_data(&mut self, _index: usize, _rect: Rect) -> Vec<u8> {
+    ///         Vec::new()
+    ///     }
+    /// }
+    ///
+    /// let mut encoder = encoder_builder().buffering(Buffering::Stream).build()?;
+    /// let mut output = WriteOutput::new(vec![]);
+    /// encoder.encode_chunked_to::<_, u8>(Gradient, 1024, 1024, &mut output)?;
+    /// # Ok(())
+    /// # };
+    /// ```
+    ///
+    /// # Errors
+    /// Return [`EncodeError`] if the internal encoder fails to encode,
+    /// [`EncodeError::BadInput`] if the source gives less data than requested, or
+    /// [`EncodeError::Io`] if it fails to write
+    pub fn encode_chunked_to<S: ChunkedSource, U: PixelType>(
+        &mut self,
+        source: S,
+        width: u32,
+        height: u32,
+        mut output: impl EncoderOutput,
+    ) -> Result<(), EncodeError> {
+        self.setup_encoder(width, height, U::bits_per_sample(), self.has_alpha)?;
+        let input = ChunkedInput::new(source);
+        self.internal_to(&mut output, |enc| enc.add_chunked_frame(&input))
+    }
 }
 
-impl Drop for JxlEncoder<'_, '_> {
+impl Drop for JxlEncoder<'_, '_, '_> {
     fn drop(&mut self) {
         unsafe { JxlEncoderDestroy(self.enc) };
     }
@@ -517,10 +876,10 @@ impl Drop for JxlEncoder<'_, '_> {
 //
 // Note: JxlEncoder is NOT Sync because the underlying C API is not safe for
 // concurrent access from multiple threads.
-unsafe impl Send for JxlEncoder<'_, '_> {}
+unsafe impl Send for JxlEncoder<'_, '_, '_> {}
 
 /// Return a [`JxlEncoderBuilder`] with default settings
-pub fn encoder_builder<'prl, 'mm>() -> JxlEncoderBuilder<'prl, 'mm> {
+pub fn encoder_builder<'prl, 'mm, 'cms>() -> JxlEncoderBuilder<'prl, 'mm, 'cms> {
     JxlEncoder::builder()
 }
 
@@ -0,0 +1,226 @@
+use std::{
+    ffi::c_void,
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+use jpegxl_sys::{
+    common::types::{JxlEndianness, JxlPixelFormat},
+    encoder::encode::JxlChunkedFrameInputSource,
+};
+
+use crate::{EncodeError, common::PixelType};
+
+/// A rectangle of a frame, in pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    /// Horizontal position of the left edge
+    pub x: usize,
+    /// Vertical position of the top edge
+    pub y: usize,
+    /// Width
+    pub width: usize,
+    /// Height
+    pub height: usize,
+}
+
+/// Source of the pixels of a frame, handing out rectangles on demand so that the whole frame
+/// does not need to be in memory, e.g. reading tiles from a large scan.
+///
+/// The encoder asks for rectangles at positions which are multiples of 8, and at most
+/// 2048 x 2048 pixels. The pixels are in native endianness, interleaved and row by row
+/// without padding.
+///
+/// Rectangles may be requested from the threads of the parallel runner, but never at the
+/// same time
+pub trait ChunkedSource: Send {
+    /// Type of the samples
+    type Pixel: PixelType;
+
+    /// Number of color channels, including the alpha channel.
+    /// The alpha channel is enabled by [`JxlEncoder::has_alpha`](super::JxlEncoder::has_alpha)
+    ///
+    /// Default: 3
+    fn num_channels(&self) -> u32 {
+        3
+    }
+
+    /// Color channel data of `rect`, with `rect.width * rect.height * num_channels` samples
+    fn color_data(&mut self, rect: Rect) -> Vec<Self::Pixel>;
+
+    /// Data of the extra channel at `index` in `rect`, with `rect.width * rect.height` samples,
+    /// for the channels set by [`JxlEncoder::extra_channels`](super::JxlEncoder::extra_channels).
+    /// It is never called without them, so such sources can return an empty `Vec`.
+    ///
+    /// An alpha channel included in [`num_channels`](Self::num_channels) is taken from the color
+    /// data and not requested here, but it still counts for `index`: with alpha, the first
+    /// extra channel is at index 1
+    fn extra_channel_data(&mut self, index: usize, rect: Rect) -> Vec<Self::Pixel>;
+}
+
+impl<S: ChunkedSource + ?Sized> ChunkedSource for &mut S {
+    type Pixel = S::Pixel;
+
+    fn num_channels(&self) -> u32 {
+        (**self).num_channels()
+    }
+
+    fn color_data(&mut self, rect: Rect) -> Vec<Self::Pixel> {
+        (**self).color_data(rect)
+    }
+
+    fn extra_channel_data(&mut self, index: usize, rect: Rect) -> Vec<Self::Pixel> {
+        (**self).extra_channel_data(index, rect)
+    }
+}
+
+/// Adapter of a [`ChunkedSource`] to the callbacks of the encoder
+pub(crate) struct ChunkedInput<S: ChunkedSource> {
+    state: Mutex<State<S>>,
+}
+
+struct State<S: ChunkedSource> {
+    source: S,
+    /// Data handed to the encoder, until it is released
+    buffers: Vec<Vec<S::Pixel>>,
+    /// Whether the source gave less data than requested
+    short: bool,
+}
+
+impl<S: ChunkedSource> ChunkedInput<S> {
+    pub(crate) fn new(source: S) -> Self {
+        Self {
+            state: Mutex::new(State {
+                source,
+                buffers: Vec::new(),
+                short: false,
+            }),
+        }
+    }
+
+    /// `self` must outlive the frame being added
+    pub(crate) fn source(&self) -> JxlChunkedFrameInputSource {
+        JxlChunkedFrameInputSource {
+            opaque: std::ptr::from_ref(self).cast_mut().cast(),
+            get_color_channels_pixel_format: Self::color_pixel_format,
+            get_color_channels_data_at: Self::color_data_at,
+            get_extra_channel_pixel_format: Self::extra_channel_pixel_format,
+            get_extra_channel_data_at: Self::extra_channel_data_at,
+            release_buffer: Self::release_buffer,
+        }
+    }
+
+    /// Return [`EncodeError::BadInput`] if the source gave less data than requested
+    pub(crate) fn check(&self) -> Result<(), EncodeError> {
+        if self.lock().short {
+            Err(EncodeError::BadInput)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State<S>> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn pixel_format(num_channels: u32) -> JxlPixelFormat {
+        JxlPixelFormat {
+            num_channels,
+            data_type: S::Pixel::pixel_type(),
+            endianness: JxlEndianness::Native,
+            align: 0,
+        }
+    }
+
+    extern "C-unwind" fn color_pixel_format(
+        opaque: *mut c_void,
+        pixel_format: *mut JxlPixelFormat,
+    ) {
+        let this = unsafe { &*opaque.cast::<Self>() };
+        let num_channels = this.lock().source.num_channels();
+        unsafe { *pixel_format = Self::pixel_format(num_channels) };
+    }
+
+    extern "C-unwind" fn color_data_at(
+        opaque: *mut c_void,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        row_offset: *mut usize,
+    ) -> *const c_void {
+        let this = unsafe { &*opaque.cast::<Self>() };
+        let mut state = this.lock();
+        let rect = Rect {
+            x,
+            y,
+            width,
+            height,
+        };
+        let num_channels = state.source.num_channels() as usize;
+        let data = state.source.color_data(rect);
+
+        unsafe { *row_offset = width * num_channels * size_of::<S::Pixel>() };
+        state.keep(data, width * height * num_channels)
+    }
+
+    extern "C-unwind" fn extra_channel_pixel_format(
+        _opaque: *mut c_void,
+        _ec_index: usize,
+        pixel_format: *mut JxlPixelFormat,
+    ) {
+        unsafe { *pixel_format = Self::pixel_format(1) };
+    }
+
+    extern "C-unwind" fn extra_channel_data_at(
+        opaque: *mut c_void,
+        ec_index: usize,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        row_offset: *mut usize,
+    ) -> *const c_void {
+        let this = unsafe { &*opaque.cast::<Self>() };
+        let mut state = this.lock();
+        let rect = Rect {
+            x,
+            y,
+            width,
+            height,
+        };
+        let data = state.source.extra_channel_data(ec_index, rect);
+
+        unsafe { *row_offset = width * size_of::<S::Pixel>() };
+        state.keep(data, width * height)
+    }
+
+    extern "C-unwind" fn release_buffer(opaque: *mut c_void, buf: *const c_void) {
+        let this = unsafe { &*opaque.cast::<Self>() };
+        let mut state = this.lock();
+        if let Some(i) = state
+            .buffers
+            .iter()
+            .position(|buffer| buffer.as_ptr().cast() == buf)
+        {
+            state.buffers.swap_remove(i);
+        }
+    }
+}
+
+impl<S: ChunkedSource> State<S> {
+    /// Keep `data` alive until it is released, padded to `len` samples so that the encoder
+    /// never reads past it
+    fn keep(&mut self, mut data: Vec<S::Pixel>, len: usize) -> *const c_void {
+        if data.len() < len {
+            self.short = true;
+            let zeros = vec![0; (len - data.len()) * size_of::<S::Pixel>()];
+            data.extend(S::Pixel::convert(
+                &zeros,
+                &ChunkedInput::<S>::pixel_format(1),
+            ));
+        }
+        let ptr = data.as_ptr().cast();
+        self.buffers.push(data);
+        ptr
+    }
+}
@@ -0,0 +1,105 @@
+use crate::decode::{BlendInfo, ExtraChannelType};
+
+/// An extra channel of the image, besides the alpha channel enabled by
+/// [`JxlEncoder::has_alpha`](super::JxlEncoder::has_alpha).
+///
+/// The pixels of each frame are given by [`EncoderFrame::extra_channel`](super::EncoderFrame::extra_channel)
+#[derive(Clone, Debug)]
+pub struct ExtraChannel {
+    pub(crate) channel_type: ExtraChannelType,
+    pub(crate) bits_per_sample: Option<(u32, u32)>,
+    pub(crate) dim_shift: u32,
+    pub(crate) name: String,
+    pub(crate) alpha_premultiplied: bool,
+    pub(crate) spot_color: [f32; 4],
+    pub(crate) cfa_channel: u32,
+    pub(crate) distance: Option<f32>,
+    pub(crate) blend_info: Option<BlendInfo>,
+}
+
+impl ExtraChannel {
+    /// Create an extra channel of the type, such as [`ExtraChannelType::Depth`],
+    /// [`ExtraChannelType::Thermal`], [`ExtraChannelType::SelectionMask`] or
+    /// [`ExtraChannelType::Black`]
+    #[must_use]
+    pub fn new(channel_type: ExtraChannelType) -> Self {
+        Self {
+            channel_type,
+            bits_per_sample: None,
+            dim_shift: 0,
+            name: String::new(),
+            alpha_premultiplied: false,
+            spot_color: [0.0; 4],
+            cfa_channel: 1,
+            distance: None,
+            blend_info: None,
+        }
+    }
+
+    /// Create a spot color channel, with the color in linear RGB and its solidity
+    /// from 0 (transparent) to 1 (opaque)
+    #[must_use]
+    pub fn spot_color(rgb: [f32; 3], solidity: f32) -> Self {
+        let [r, g, b] = rgb;
+        Self {
+            spot_color: [r, g, b, solidity],
+            ..Self::new(ExtraChannelType::SpotColor)
+        }
+    }
+
+    /// Set the number of bits and exponent bits per sample.
+    ///
+    /// Default: the same as the color channels
+    #[must_use]
+    pub fn bits_per_sample(mut self, bits: u32, exponent_bits: u32) -> Self {
+        self.bits_per_sample = Some((bits, exponent_bits));
+        self
+    }
+
+    /// Downsample the channel by `1 << dim_shift` in the codestream
+    #[must_use]
+    pub fn dim_shift(mut self, dim_shift: u32) -> Self {
+        self.dim_shift = dim_shift;
+        self
+    }
+
+    /// Set the name of the channel
+    #[must_use]
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// Set whether the alpha channel is premultiplied.
+    /// Only applies to [`ExtraChannelType::Alpha`]
+    #[must_use]
+    pub fn alpha_premultiplied(mut self, value: bool) -> Self {
+        self.alpha_premultiplied = value;
+        self
+    }
+
+    /// Set the index of the color filter array channel.
+    /// Only applies to [`ExtraChannelType::Cfa`]
+    #[must_use]
+    pub fn cfa_channel(mut self, value: u32) -> Self {
+        self.cfa_channel = value;
+        self
+    }
+
+    /// Set the distance of the lossy compression of the channel, in the same range as
+    /// [`JxlEncoder::quality`](super::JxlEncoder::quality). 0.0 is lossless.
+    ///
+    /// Default: the same as the color channels
+    #[must_use]
+    pub fn distance(mut self, distance: f32) -> Self {
+        self.distance = Some(distance);
+        self
+    }
+
+    /// Set how the channel is blended with earlier frames
+    #[must_use]
+    pub fn blend_info(mut self, blend_info: BlendInfo) -> Self {
+        self.blend_info = Some(blend_info);
+        self
+    }
+}
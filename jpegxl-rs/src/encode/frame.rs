@@ -4,15 +4,19 @@ use jpegxl_sys::common::types::{JxlEndianness, JxlPixelFormat};
 
 use crate::{EncodeError, common::PixelType};
 
-use super::{EncoderResult, JxlEncoder};
+use super::{EncoderOutput, EncoderResult, JxlEncoder};
 
 /// A frame for the encoder, consisting of the pixels and its options
 #[allow(clippy::module_name_repetitions)]
 pub struct EncoderFrame<'data, T: PixelType> {
     pub(crate) data: &'data [T],
+    pub(crate) extra_channels: Vec<&'data [T]>,
     num_channels: Option<u32>,
     endianness: Option<JxlEndianness>,
     align: Option<usize>,
+    pub(crate) duration: u32,
+    pub(crate) timecode: u32,
+    pub(crate) name: Option<&'data str>,
 }
 
 impl<'data, T: PixelType> EncoderFrame<'data, T> {
@@ -22,9 +26,13 @@ impl<'data, T: PixelType> EncoderFrame<'data, T> {
     pub fn new(data: &'data [T]) -> Self {
         Self {
             data,
+            extra_channels: Vec::new(),
             num_channels: None,
             endianness: None,
             align: None,
+            duration: 0,
+            timecode: 0,
+            name: None,
         }
     }
 
@@ -37,6 +45,15 @@ impl<'data, T: PixelType> EncoderFrame<'data, T> {
         self
     }
 
+    /// Add the pixels of the next extra channel in
+    /// [`JxlEncoder::extra_channels`](super::JxlEncoder::extra_channels), one sample per pixel.
+    /// The endianness and the alignment are the same as the color channels
+    #[must_use]
+    pub fn extra_channel(mut self, data: &'data [T]) -> Self {
+        self.extra_channels.push(data);
+        self
+    }
+
     /// Set the endianness of the source.
     #[must_use]
     pub fn endianness(mut self, value: JxlEndianness) -> Self {
@@ -52,6 +69,30 @@ impl<'data, T: PixelType> EncoderFrame<'data, T> {
         self
     }
 
+    /// Set the duration of the frame in ticks of
+    /// [`JxlEncoder::animation`](super::JxlEncoder::animation).
+    /// Only applies to animations
+    #[must_use]
+    pub fn duration(mut self, ticks: u32) -> Self {
+        self.duration = ticks;
+        self
+    }
+
+    /// Set the SMPTE timecode of the frame, packed as `0xHHMMSSFF`.
+    /// Only applies to animations with [`have_timecodes`](super::Animation::have_timecodes)
+    #[must_use]
+    pub fn timecode(mut self, value: u32) -> Self {
+        self.timecode = value;
+        self
+    }
+
+    /// Set the name of the frame
+    #[must_use]
+    pub fn name(mut self, value: &'data str) -> Self {
+        self.name = Some(value);
+        self
+    }
+
     pub(crate) fn pixel_format(&self) -> JxlPixelFormat {
         JxlPixelFormat {
             num_channels: self.num_channels.unwrap_or(3),
@@ -63,15 +104,16 @@ impl<'data, T: PixelType> EncoderFrame<'data, T> {
 }
 
 /// A wrapper type for encoding multiple frames
-pub struct MultiFrames<'enc, 'prl, 'mm, U>(
-    pub(crate) &'enc mut JxlEncoder<'prl, 'mm>,
+pub struct MultiFrames<'enc, 'prl, 'mm, 'cms, U>(
+    pub(crate) &'enc mut JxlEncoder<'prl, 'mm, 'cms>,
     pub(crate) PhantomData<U>,
 )
 where
     'prl: 'enc,
-    'mm: 'enc;
+    'mm: 'enc,
+    'cms: 'enc;
 
-impl<U: PixelType> MultiFrames<'_, '_, '_, U> {
+impl<U: PixelType> MultiFrames<'_, '_, '_, '_, U> {
     /// Add a frame to the encoder
     /// # Errors
     /// Return [`EncodeError`] if the internal encoder fails to add a frame
@@ -94,4 +136,12 @@ impl<U: PixelType> MultiFrames<'_, '_, '_, U> {
     pub fn encode(self) -> Result<EncoderResult<U>, EncodeError> {
         self.0.start_encoding()
     }
+
+    /// Encode a JPEG XL image from the frames, writing to `output` as the data is produced
+    /// # Errors
+    /// Return [`EncodeError`] if the internal encoder fails to encode, or
+    /// [`EncodeError::Io`] if it fails to write
+    pub fn encode_to(self, mut output: impl EncoderOutput) -> Result<(), EncodeError> {
+        self.0.internal_to(&mut output, |_| Ok(()))
+    }
 }
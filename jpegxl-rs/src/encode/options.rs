@@ -1,6 +1,10 @@
 use std::mem::MaybeUninit;
 
-use jpegxl_sys::{color::color_encoding::JxlColorEncoding, encoder::encode as api};
+use jpegxl_sys::{
+    color::color_encoding::{JxlColorEncoding, JxlPrimaries, JxlTransferFunction},
+    encoder::encode as api,
+    metadata::codestream_header::JxlAnimationHeader,
+};
 
 /// Encoding speed
 #[derive(Debug, Clone, Copy, Default)]
@@ -28,8 +32,64 @@ pub enum EncoderSpeed {
     Glacier,
 }
 
+/// Buffering strategy of the encoder, trading compression density for memory usage.
+///
+/// Streaming needs the input from a [`ChunkedSource`](super::ChunkedSource) and an
+/// [`EncoderOutput`](super::EncoderOutput) to take effect. Images encoded with streaming
+/// might not be progressively decodable
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Buffering {
+    /// Chosen by the encoder, default
+    #[default]
+    Auto = -1,
+    /// Buffer the whole image, for the best compression density
+    Full = 0,
+    /// Buffer images smaller than 2048 x 2048, and stream larger ones
+    StreamLarge = 1,
+    /// Stream all images larger than one group, i.e. 256 x 256 pixels by default
+    Stream = 2,
+}
+
+/// Animation settings, making the frames of the image play one after another
+///
+/// Default: 100 ticks per second, looping forever, without timecodes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Animation {
+    /// Numerator of the ticks per second, which are the unit of the frame durations
+    pub tps_numerator: u32,
+    /// Denominator of the ticks per second
+    pub tps_denominator: u32,
+    /// Number of times to play the animation, 0 to loop forever
+    pub num_loops: u32,
+    /// Whether the frames have SMPTE timecodes
+    pub have_timecodes: bool,
+}
+
+impl Default for Animation {
+    fn default() -> Self {
+        Self {
+            tps_numerator: 100,
+            tps_denominator: 1,
+            num_loops: 0,
+            have_timecodes: false,
+        }
+    }
+}
+
+impl From<&Animation> for JxlAnimationHeader {
+    fn from(val: &Animation) -> Self {
+        Self {
+            tps_numerator: val.tps_numerator,
+            tps_denominator: val.tps_denominator,
+            num_loops: val.num_loops,
+            have_timecodes: val.have_timecodes.into(),
+        }
+    }
+}
+
 /// Encoding color profile
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub enum ColorEncoding {
     /// sRGB, default for int pixel types
     Srgb,
@@ -39,13 +99,28 @@ pub enum ColorEncoding {
     SrgbLuma,
     /// Linear sRGB with only luma channel
     LinearSrgbLuma,
+    /// Display P3, with P3 primaries, D65 white point and sRGB transfer function
+    DisplayP3,
+    /// Rec. 2100 with the PQ transfer function
+    Rec2100Pq,
+    /// Rec. 2100 with the HLG transfer function
+    Rec2100Hlg,
     /// Custom
     Custom(JxlColorEncoding),
 }
 
 impl From<&ColorEncoding> for JxlColorEncoding {
     fn from(val: &ColorEncoding) -> Self {
-        use ColorEncoding::{Custom, LinearSrgb, LinearSrgbLuma, Srgb, SrgbLuma};
+        use ColorEncoding::{
+            Custom, DisplayP3, LinearSrgb, LinearSrgbLuma, Rec2100Hlg, Rec2100Pq, Srgb, SrgbLuma,
+        };
+
+        let rgb = |primaries, transfer_function| {
+            let mut color_encoding = JxlColorEncoding::from(&Srgb);
+            color_encoding.primaries = primaries;
+            color_encoding.transfer_function = transfer_function;
+            color_encoding
+        };
 
         let mut color_encoding = MaybeUninit::uninit();
 
@@ -61,6 +136,9 @@ impl From<&ColorEncoding> for JxlColorEncoding {
                 LinearSrgbLuma => {
                     api::JxlColorEncodingSetToLinearSRGB(color_encoding.as_mut_ptr(), true.into());
                 }
+                DisplayP3 => return rgb(JxlPrimaries::P3, JxlTransferFunction::SRGB),
+                Rec2100Pq => return rgb(JxlPrimaries::Rec2100, JxlTransferFunction::PQ),
+                Rec2100Hlg => return rgb(JxlPrimaries::Rec2100, JxlTransferFunction::HLG),
                 Custom(e) => {
                     return e.clone();
                 }
@@ -0,0 +1,310 @@
+use std::{
+    ffi::c_void,
+    io::{self, Seek, SeekFrom, Write},
+    ptr::null_mut,
+};
+
+use jpegxl_sys::encoder::encode::JxlEncoderOutputProcessor;
+
+use crate::{common::CancellationToken, errors::EncodeError};
+
+/// Size of the buffer handed to the encoder when streaming
+const WRITE_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Destination of the encoded data, which receives the output as soon as it is produced
+/// instead of collecting it in memory.
+///
+/// [`WriteOutput`] and [`SeekOutput`] adapt [`Write`] and [`Write`] + [`Seek`] types
+pub trait EncoderOutput {
+    /// Write all of `data` at the current position
+    ///
+    /// # Errors
+    /// The error stops the encoder and is returned as [`EncodeError::Io`]
+    fn write(&mut self, data: &[u8]) -> io::Result<()>;
+
+    /// Move the current position to `position`, counted from the start of the output,
+    /// to fill in data written earlier. Only called if [`seekable`](Self::seekable) is `true`
+    ///
+    /// # Errors
+    /// The error stops the encoder and is returned as [`EncodeError::Io`]
+    fn seek(&mut self, position: u64) -> io::Result<()> {
+        let _ = position;
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    /// Whether [`seek`](Self::seek) is supported. Otherwise the encoder holds back the data
+    /// which needs to be filled in later, until it is complete
+    fn seekable(&self) -> bool {
+        false
+    }
+
+    /// The data before `position` will not be written again, so it can be uploaded or freed
+    ///
+    /// # Errors
+    /// The error stops the encoder and is returned as [`EncodeError::Io`]
+    fn finalized(&mut self, position: u64) -> io::Result<()> {
+        let _ = position;
+        Ok(())
+    }
+}
+
+impl<O: EncoderOutput + ?Sized> EncoderOutput for &mut O {
+    fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        (**self).write(data)
+    }
+
+    fn seek(&mut self, position: u64) -> io::Result<()> {
+        (**self).seek(position)
+    }
+
+    fn seekable(&self) -> bool {
+        (**self).seekable()
+    }
+
+    fn finalized(&mut self, position: u64) -> io::Result<()> {
+        (**self).finalized(position)
+    }
+}
+
+/// [`EncoderOutput`] writing to a [`Write`] sequentially, such as a socket
+pub struct WriteOutput<W>(W);
+
+impl<W: Write> WriteOutput<W> {
+    /// Write the output to `writer`
+    pub fn new(writer: W) -> Self {
+        Self(writer)
+    }
+
+    /// Return the underlying writer
+    pub fn into_inner(self) -> W {
+        self.0
+    }
+}
+
+impl<W: Write> EncoderOutput for WriteOutput<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        self.0.write_all(data)
+    }
+
+    fn finalized(&mut self, _position: u64) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// [`EncoderOutput`] writing to a [`Write`] + [`Seek`], such as a file.
+/// The output starts at the position of the writer when it is created
+pub struct SeekOutput<W> {
+    writer: W,
+    start: u64,
+}
+
+impl<W: Write + Seek> SeekOutput<W> {
+    /// Write the output to `writer`, from its current position
+    ///
+    /// # Errors
+    /// Return the error of querying the position of `writer`
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        let start = writer.stream_position()?;
+        Ok(Self { writer, start })
+    }
+
+    /// Return the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write + Seek> EncoderOutput for SeekOutput<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        self.writer.write_all(data)
+    }
+
+    fn seek(&mut self, position: u64) -> io::Result<()> {
+        self.writer.seek(SeekFrom::Start(self.start + position))?;
+        Ok(())
+    }
+
+    fn seekable(&self) -> bool {
+        true
+    }
+
+    fn finalized(&mut self, _position: u64) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Output processor collecting the encoded data in memory.
+/// It stops the encoder by refusing to give out buffers once cancelled
+pub(crate) struct OutputBuffer {
+    data: Vec<u8>,
+    /// Position of the next write
+    position: usize,
+    /// End of the written data
+    len: usize,
+    cancel: Option<CancellationToken>,
+    cancelled: bool,
+}
+
+impl OutputBuffer {
+    pub(crate) fn new(capacity: usize, cancel: Option<CancellationToken>) -> Self {
+        Self {
+            data: Vec::with_capacity(capacity),
+            position: 0,
+            len: 0,
+            cancel,
+            cancelled: false,
+        }
+    }
+
+    /// `self` must outlive the encoding
+    pub(crate) fn processor(&mut self) -> JxlEncoderOutputProcessor {
+        JxlEncoderOutputProcessor {
+            opaque: std::ptr::from_mut(self).cast(),
+            get_buffer: Self::get_buffer,
+            release_buffer: Self::release_buffer,
+            seek: Some(Self::seek),
+            set_finalized_position: Self::set_finalized_position,
+        }
+    }
+
+    /// Whether the encoder was stopped by the cancellation token
+    pub(crate) fn cancelled(&self) -> bool {
+        self.cancelled
+    }
+
+    pub(crate) fn into_inner(mut self) -> Vec<u8> {
+        self.data.truncate(self.len);
+        self.data.shrink_to_fit();
+        self.data
+    }
+
+    extern "C-unwind" fn get_buffer(opaque: *mut c_void, size: *mut usize) -> *mut c_void {
+        let this = unsafe { &mut *opaque.cast::<Self>() };
+        if is_cancelled(this.cancel.as_ref()) {
+            this.cancelled = true;
+            unsafe { *size = 0 };
+            return null_mut();
+        }
+
+        let end = this.position + unsafe { *size };
+        if this.data.len() < end {
+            this.data.resize(end, 0);
+        }
+        this.data[this.position..].as_mut_ptr().cast()
+    }
+
+    extern "C-unwind" fn release_buffer(opaque: *mut c_void, written_bytes: usize) {
+        let this = unsafe { &mut *opaque.cast::<Self>() };
+        this.position += written_bytes;
+        this.len = this.len.max(this.position);
+    }
+
+    extern "C-unwind" fn seek(opaque: *mut c_void, position: u64) {
+        let this = unsafe { &mut *opaque.cast::<Self>() };
+        // Only seeks back to the data written, which is in memory
+        #[allow(clippy::cast_possible_truncation)]
+        let position = position as usize;
+        this.position = position;
+    }
+
+    extern "C-unwind" fn set_finalized_position(_opaque: *mut c_void, _finalized_position: u64) {}
+}
+
+/// Output processor passing the encoded data to an [`EncoderOutput`] through a small buffer.
+/// It stops the encoder by refusing to give out buffers once cancelled or failed
+pub(crate) struct StreamOutput<'out> {
+    output: &'out mut dyn EncoderOutput,
+    buffer: Vec<u8>,
+    cancel: Option<CancellationToken>,
+    cancelled: bool,
+    error: Option<io::Error>,
+}
+
+impl<'out> StreamOutput<'out> {
+    pub(crate) fn new(
+        output: &'out mut dyn EncoderOutput,
+        cancel: Option<CancellationToken>,
+    ) -> Self {
+        Self {
+            output,
+            buffer: Vec::new(),
+            cancel,
+            cancelled: false,
+            error: None,
+        }
+    }
+
+    /// `self` must outlive the encoding
+    pub(crate) fn processor(&mut self) -> JxlEncoderOutputProcessor {
+        JxlEncoderOutputProcessor {
+            opaque: std::ptr::from_mut(self).cast(),
+            get_buffer: Self::get_buffer,
+            release_buffer: Self::release_buffer,
+            seek: self
+                .output
+                .seekable()
+                .then_some(Self::seek as extern "C-unwind" fn(*mut c_void, u64)),
+            set_finalized_position: Self::set_finalized_position,
+        }
+    }
+
+    /// Combine the status of the encoder with the errors of the output
+    pub(crate) fn finish(self, status: Result<(), EncodeError>) -> Result<(), EncodeError> {
+        if self.cancelled {
+            return Err(EncodeError::Cancelled);
+        }
+        if let Some(err) = self.error {
+            return Err(err.into());
+        }
+        status
+    }
+
+    fn record(&mut self, result: io::Result<()>) {
+        if let Err(err) = result {
+            self.error.get_or_insert(err);
+        }
+    }
+
+    extern "C-unwind" fn get_buffer(opaque: *mut c_void, size: *mut usize) -> *mut c_void {
+        let this = unsafe { &mut *opaque.cast::<Self>() };
+        if is_cancelled(this.cancel.as_ref()) {
+            this.cancelled = true;
+        }
+        if this.cancelled || this.error.is_some() {
+            unsafe { *size = 0 };
+            return null_mut();
+        }
+
+        // The encoder copies through its own buffer if it needs more at once
+        let len = unsafe { *size }.min(WRITE_CHUNK_SIZE);
+        this.buffer.resize(len, 0);
+        unsafe { *size = len };
+        this.buffer.as_mut_ptr().cast()
+    }
+
+    extern "C-unwind" fn release_buffer(opaque: *mut c_void, written_bytes: usize) {
+        let this = unsafe { &mut *opaque.cast::<Self>() };
+        let result = this.output.write(&this.buffer[..written_bytes]);
+        this.record(result);
+    }
+
+    extern "C-unwind" fn seek(opaque: *mut c_void, position: u64) {
+        let this = unsafe { &mut *opaque.cast::<Self>() };
+        if this.error.is_none() {
+            let result = this.output.seek(position);
+            this.record(result);
+        }
+    }
+
+    extern "C-unwind" fn set_finalized_position(opaque: *mut c_void, finalized_position: u64) {
+        let this = unsafe { &mut *opaque.cast::<Self>() };
+        if this.error.is_none() {
+            let result = this.output.finalized(finalized_position);
+            this.record(result);
+        }
+    }
+}
+
+fn is_cancelled(cancel: Option<&CancellationToken>) -> bool {
+    cancel.is_some_and(CancellationToken::is_cancelled)
+}
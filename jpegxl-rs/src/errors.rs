@@ -49,6 +49,75 @@ pub enum DecodeError {
     /// Feature not yet implemented in this wrapper
     #[error("Feature not yet implemented: {0}")]
     NotImplemented(&'static str),
+    /// Output buffer cannot hold the decoded image
+    #[error("Output buffer is too small: {actual} bytes given, {expected} bytes needed")]
+    BufferTooSmall {
+        /// Size needed for the image
+        expected: usize,
+        /// Size of the given buffer
+        actual: usize,
+    },
+    /// Row stride is shorter than a row of pixels
+    #[error("Row stride of {stride} bytes is shorter than a row of {row} bytes")]
+    InvalidStride {
+        /// Given stride in bytes
+        stride: usize,
+        /// Size of a row in bytes
+        row: usize,
+    },
+    /// A resource limit set in the decoder is exceeded
+    #[error("Limit of {kind} exceeded: {actual} > {limit}")]
+    LimitExceeded {
+        /// What is limited
+        kind: LimitKind,
+        /// The limit
+        limit: u64,
+        /// Value in the image
+        actual: u64,
+    },
+    /// Cancelled by a [`CancellationToken`](crate::CancellationToken)
+    #[error("Decoding is cancelled")]
+    Cancelled,
+    /// Input is fed after it was closed
+    #[error("Input is already closed")]
+    InputClosed,
+    /// Failed to read input
+    #[error("Failed to read input: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Resource limited by the decoder, reported by [`DecodeError::LimitExceeded`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LimitKind {
+    /// Width of the image or a frame
+    Width,
+    /// Height of the image or a frame
+    Height,
+    /// Number of pixels of the image or a frame
+    Pixels,
+    /// Number of frames
+    Frames,
+    /// Number of extra channels
+    ExtraChannels,
+    /// Size of the ICC profile in bytes
+    IccSize,
+    /// Number of bytes allocated for the outputs
+    OutputBytes,
+}
+
+impl std::fmt::Display for LimitKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Width => "width",
+            Self::Height => "height",
+            Self::Pixels => "pixels",
+            Self::Frames => "frames",
+            Self::ExtraChannels => "extra channels",
+            Self::IccSize => "ICC profile size",
+            Self::OutputBytes => "output bytes",
+        })
+    }
 }
 
 /// Errors derived from [`JxlEncoderStatus`][jpegxl_sys::encoder::encode::JxlEncoderStatus]
@@ -84,6 +153,12 @@ pub enum EncodeError {
     /// a debug build of libjxl should output a specific error message
     #[error("The encoder API is used in an incorrect way")]
     ApiUsage,
+    /// Cancelled by a [`CancellationToken`](crate::CancellationToken)
+    #[error("Encoding is cancelled")]
+    Cancelled,
+    /// Failed to write output to an [`EncoderOutput`](crate::encode::EncoderOutput)
+    #[error("Failed to write output: {0}")]
+    Io(#[from] std::io::Error),
     /// Unknown status
     #[error("Unknown status: `{0:?}`")]
     UnknownStatus(JxlEncoderError),
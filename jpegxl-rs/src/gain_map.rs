@@ -0,0 +1,211 @@
+/*
+ * This file is part of jpegxl-rs.
+ *
+ * jpegxl-rs is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * jpegxl-rs is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with jpegxl-rs.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Gain map (`jhgm` box) support
+//!
+//! A gain map lets an HDR image be rendered on SDR displays, or the other way around, by
+//! applying a per-pixel gain to the base image. It is stored in a `jhgm` box, added by
+//! [`JxlEncoder::add_gain_map`](crate::encode::JxlEncoder::add_gain_map) and read by
+//! [`Metadata::gain_map`](crate::decode::Metadata::gain_map).
+
+use std::{mem::MaybeUninit, ptr::null};
+
+use jpegxl_sys::{
+    color::color_encoding::JxlColorEncoding,
+    common::types::JxlBool,
+    metadata::gain_map::{
+        JxlGainMapBundle, JxlGainMapGetBundleSize, JxlGainMapReadBundle, JxlGainMapWriteBundle,
+    },
+};
+
+use crate::{
+    encode::ColorEncoding,
+    errors::{DecodeError, EncodeError},
+};
+
+/// Box type of a gain map
+pub(crate) const GAIN_MAP_BOX: [u8; 4] = *b"jhgm";
+
+/// Gain map bundle
+#[derive(Clone, Debug, Default)]
+pub struct GainMap {
+    /// Version of the bundle, currently `0`
+    pub version: u8,
+    /// Gain map metadata, a binary blob following ISO 21496-1
+    pub metadata: Vec<u8>,
+    /// Color encoding of the alternate rendition
+    pub color_encoding: Option<JxlColorEncoding>,
+    /// ICC profile of the alternate rendition, compressed as in a JPEG XL codestream.
+    /// Empty if not present
+    pub alt_icc: Vec<u8>,
+    /// The gain map itself, a bare JPEG XL codestream
+    pub gain_map: Vec<u8>,
+}
+
+impl GainMap {
+    /// Parse a gain map from the contents of a `jhgm` box
+    ///
+    /// # Errors
+    /// Return [`DecodeError::InvalidInput`] if the data is not a valid gain map bundle
+    pub fn from_bytes(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut bundle = MaybeUninit::<JxlGainMapBundle>::uninit();
+        let mut bytes_read = 0;
+        if unsafe {
+            JxlGainMapReadBundle(
+                bundle.as_mut_ptr(),
+                data.as_ptr(),
+                data.len(),
+                &raw mut bytes_read,
+            )
+        } != JxlBool::True
+        {
+            return Err(DecodeError::InvalidInput);
+        }
+
+        // The pointers in the bundle point into `data`. The color encoding is only written when
+        // it is present, so the fields are read one by one instead of the whole bundle
+        let bundle = bundle.as_ptr();
+        let copy = |ptr: *const u8, len: usize| {
+            if ptr.is_null() || len == 0 {
+                Vec::new()
+            } else {
+                unsafe { std::slice::from_raw_parts(ptr, len) }.to_vec()
+            }
+        };
+
+        unsafe {
+            Ok(Self {
+                version: (*bundle).jhgm_version,
+                metadata: copy(
+                    (*bundle).gain_map_metadata,
+                    (*bundle).gain_map_metadata_size.into(),
+                ),
+                color_encoding: ((*bundle).has_color_encoding == JxlBool::True)
+                    .then(|| (&raw const (*bundle).color_encoding).read()),
+                alt_icc: copy((*bundle).alt_icc, (*bundle).alt_icc_size as usize),
+                gain_map: copy((*bundle).gain_map, (*bundle).gain_map_size as usize),
+            })
+        }
+    }
+
+    /// Serialize the gain map to the contents of a `jhgm` box
+    ///
+    /// # Errors
+    /// Return [`EncodeError::BadInput`] if a part is too large to be stored
+    pub fn to_bytes(&self) -> Result<Vec<u8>, EncodeError> {
+        let bundle = JxlGainMapBundle {
+            jhgm_version: self.version,
+            gain_map_metadata_size: self
+                .metadata
+                .len()
+                .try_into()
+                .map_err(|_| EncodeError::BadInput)?,
+            gain_map_metadata: non_null(&self.metadata),
+            has_color_encoding: self.color_encoding.is_some().into(),
+            color_encoding: self
+                .color_encoding
+                .clone()
+                .unwrap_or_else(|| (&ColorEncoding::Srgb).into()),
+            alt_icc_size: self
+                .alt_icc
+                .len()
+                .try_into()
+                .map_err(|_| EncodeError::BadInput)?,
+            alt_icc: non_null(&self.alt_icc),
+            gain_map_size: self
+                .gain_map
+                .len()
+                .try_into()
+                .map_err(|_| EncodeError::BadInput)?,
+            gain_map: non_null(&self.gain_map),
+        };
+
+        let mut size = 0;
+        if unsafe { JxlGainMapGetBundleSize(&raw const bundle, &raw mut size) } != JxlBool::True {
+            return Err(EncodeError::BadInput);
+        }
+
+        let mut buf = vec![0; size];
+        let mut bytes_written = 0;
+        if unsafe {
+            JxlGainMapWriteBundle(
+                &raw const bundle,
+                buf.as_mut_ptr(),
+                buf.len(),
+                &raw mut bytes_written,
+            )
+        } != JxlBool::True
+        {
+            return Err(EncodeError::BadInput);
+        }
+        buf.truncate(bytes_written);
+
+        Ok(buf)
+    }
+}
+
+/// Pointer to the data, or null if it is empty
+fn non_null(data: &[u8]) -> *const u8 {
+    if data.is_empty() {
+        null()
+    } else {
+        data.as_ptr()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use testresult::TestResult;
+
+    use super::*;
+
+    #[test]
+    fn round_trip() -> TestResult {
+        let gain_map = GainMap {
+            metadata: vec![1, 2, 3],
+            color_encoding: Some((&ColorEncoding::LinearSrgb).into()),
+            gain_map: vec![0xff, 0x0a, 4, 5, 6],
+            ..Default::default()
+        };
+
+        let data = gain_map.to_bytes()?;
+        let parsed = GainMap::from_bytes(&data)?;
+        assert_eq!(parsed.version, 0);
+        assert_eq!(parsed.metadata, gain_map.metadata);
+        assert!(parsed.color_encoding.is_some());
+        assert!(parsed.alt_icc.is_empty());
+        assert_eq!(parsed.gain_map, gain_map.gain_map);
+
+        // Without a color encoding
+        let gain_map = GainMap {
+            color_encoding: None,
+            ..gain_map
+        };
+        let parsed = GainMap::from_bytes(&gain_map.to_bytes()?)?;
+        assert!(parsed.color_encoding.is_none());
+        assert_eq!(parsed.metadata, gain_map.metadata);
+        assert_eq!(parsed.gain_map, gain_map.gain_map);
+
+        assert!(matches!(
+            GainMap::from_bytes(&[]),
+            Err(DecodeError::InvalidInput)
+        ));
+
+        Ok(())
+    }
+}
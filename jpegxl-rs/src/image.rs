@@ -17,15 +17,31 @@
 
 //! `image` crate integration
 
-use std::mem::MaybeUninit;
+use std::{
+    io::{Read, Write},
+    mem::MaybeUninit,
+};
 
-use image::{DynamicImage, ImageBuffer};
+use image::{
+    ColorType, DynamicImage, ExtendedColorType, ImageBuffer, ImageDecoder, ImageEncoder,
+    ImageError, ImageResult, LimitSupport, Limits,
+    error::{
+        DecodingError, EncodingError, ImageFormatHint, LimitError, LimitErrorKind,
+        UnsupportedError, UnsupportedErrorKind,
+    },
+    hooks,
+    metadata::Orientation,
+};
 use jpegxl_sys::common::types::{JxlDataType, JxlPixelFormat};
 
 use crate::{
-    DecodeError,
-    common::PixelType,
-    decode::{JxlDecoder, Metadata},
+    DecodeError, EncodeError, LimitKind,
+    common::{Endianness, PixelType},
+    decode::{
+        ImageInfo, Input, JxlDecoder, Metadata, MetadataBox, PixelFormat, ReaderInput, SliceInput,
+        Steps,
+    },
+    encode::{ColorEncoding, EncoderFrame, JxlEncoder, Metadata as EncoderMetadata},
 };
 
 /// Extension trait for [`JxlDecoder`]
@@ -46,45 +62,75 @@ pub trait ToDynamic {
         &self,
         data: &[u8],
     ) -> Result<Option<DynamicImage>, DecodeError>;
+
+    /// Decode the JPEG XL image from a reader to a [`DynamicImage`]
+    ///
+    /// # Errors
+    /// Return a [`DecodeError`] when internal decoding or reading fails.
+    /// Return `Ok(None)` when the image is not representable as a [`DynamicImage`]
+    fn decode_reader_to_image<R: Read>(
+        &self,
+        reader: R,
+    ) -> Result<Option<DynamicImage>, DecodeError>;
+
+    /// Decode the JPEG XL image from a reader to a [`DynamicImage`] with a specific pixel type
+    ///
+    /// # Errors
+    /// Return a [`DecodeError`] when internal decoding or reading fails.
+    /// Return `Ok(None)` when the image is not representable as a [`DynamicImage`]
+    fn decode_reader_to_image_with<T: PixelType, R: Read>(
+        &self,
+        reader: R,
+    ) -> Result<Option<DynamicImage>, DecodeError>;
 }
 
-impl ToDynamic for JxlDecoder<'_, '_> {
+impl ToDynamic for JxlDecoder<'_, '_, '_> {
     fn decode_to_image(&self, data: &[u8]) -> Result<Option<DynamicImage>, DecodeError> {
-        let mut buffer = vec![];
-        let mut pixel_format = MaybeUninit::uninit();
-        let metadata = self.decode_internal(
-            data,
-            None,
-            false,
-            None,
-            pixel_format.as_mut_ptr(),
-            &mut buffer,
-        )?;
-
-        let pixel_format = unsafe { pixel_format.assume_init() };
-        Ok(to_image(metadata, &pixel_format, buffer))
+        decode_input_to_image(self, SliceInput::new(data), None)
     }
 
     fn decode_to_image_with<T: PixelType>(
         &self,
         data: &[u8],
     ) -> Result<Option<DynamicImage>, DecodeError> {
-        let mut buffer = vec![];
-        let mut pixel_format = MaybeUninit::uninit();
-        let metadata = self.decode_internal(
-            data,
-            Some(T::pixel_type()),
-            false,
-            None,
-            pixel_format.as_mut_ptr(),
-            &mut buffer,
-        )?;
+        decode_input_to_image(self, SliceInput::new(data), Some(T::pixel_type()))
+    }
+
+    fn decode_reader_to_image<R: Read>(
+        &self,
+        reader: R,
+    ) -> Result<Option<DynamicImage>, DecodeError> {
+        decode_input_to_image(self, ReaderInput::new(reader), None)
+    }
 
-        let pixel_format = unsafe { pixel_format.assume_init() };
-        Ok(to_image(metadata, &pixel_format, buffer))
+    fn decode_reader_to_image_with<T: PixelType, R: Read>(
+        &self,
+        reader: R,
+    ) -> Result<Option<DynamicImage>, DecodeError> {
+        decode_input_to_image(self, ReaderInput::new(reader), Some(T::pixel_type()))
     }
 }
 
+fn decode_input_to_image(
+    decoder: &JxlDecoder,
+    input: impl Input,
+    data_type: Option<JxlDataType>,
+) -> Result<Option<DynamicImage>, DecodeError> {
+    let mut buffer = vec![];
+    let mut pixel_format = MaybeUninit::uninit();
+    let metadata = decoder.decode_internal(
+        input,
+        data_type,
+        false,
+        None,
+        pixel_format.as_mut_ptr(),
+        &mut buffer,
+    )?;
+
+    let pixel_format = unsafe { pixel_format.assume_init() };
+    Ok(to_image(metadata, &pixel_format, buffer))
+}
+
 fn to_image(
     Metadata { width, height, .. }: Metadata,
     pixel_format: &JxlPixelFormat,
@@ -131,6 +177,398 @@ fn to_image(
     }
 }
 
+/// Decoder for the [`image`] crate, reading a JPEG XL image from a reader
+///
+/// The header, the ICC profile and the metadata boxes before the codestream are read when it
+/// is created, and the rest of the input while reading the pixels. Only the input which is not
+/// decoded yet is buffered, at most a few chunks of 64 KiB. Metadata boxes after the codestream
+/// are not available.
+///
+/// The pixels are oriented by `libjxl`, so [`orientation`](ImageDecoder::orientation) is
+/// [`Orientation::NoTransforms`], unless `skip_reorientation` is set in the decoder.
+/// Grayscale float images are decoded as 16-bit integers, which [`ColorType`] has no float
+/// variant for.
+///
+/// # Example
+/// ```
+/// # || -> Result<(), Box<dyn std::error::Error>> {
+/// use image::DynamicImage;
+/// use jpegxl_rs::image::JxlImageDecoder;
+///
+/// let sample = include_bytes!("../../samples/sample.jxl");
+/// let decoder = JxlImageDecoder::new(&sample[..])?;
+/// let img = DynamicImage::from_decoder(decoder)?;
+/// # Ok(())
+/// # };
+/// ```
+pub struct JxlImageDecoder<'pr, 'mm, 'cms, R> {
+    decoder: JxlDecoder<'pr, 'mm, 'cms>,
+    /// Decoding paused after the header
+    steps: Steps<ReaderInput<R>>,
+    info: ImageInfo,
+    metadata: Metadata,
+    color_type: ColorType,
+}
+
+impl<R: Read> JxlImageDecoder<'_, '_, '_, R> {
+    /// Read the header of the image from `reader` with a default decoder
+    ///
+    /// # Errors
+    /// Return a [`DecodeError`] if it fails to read the input or the header of the image
+    pub fn new(reader: R) -> Result<Self, DecodeError> {
+        JxlImageDecoder::with_decoder(JxlDecoder::builder().build()?, reader)
+    }
+}
+
+impl<'pr, 'mm, 'cms, R: Read> JxlImageDecoder<'pr, 'mm, 'cms, R> {
+    /// Read the header of the image from `reader` with the settings of `decoder`.
+    /// Its pixel format is replaced by the one of the image
+    ///
+    /// # Errors
+    /// Return a [`DecodeError`] if it fails to read the input or the header of the image
+    pub fn with_decoder(
+        mut decoder: JxlDecoder<'pr, 'mm, 'cms>,
+        reader: R,
+    ) -> Result<Self, DecodeError> {
+        let (steps, info) = Steps::header(&decoder, ReaderInput::new(reader))?;
+        let metadata = steps.metadata()?;
+        let color_type = color_type(&info);
+        decoder.pixel_format = Some(PixelFormat {
+            num_channels: color_type.channel_count().into(),
+            endianness: Endianness::Native,
+            align: 0,
+        });
+
+        Ok(Self {
+            decoder,
+            steps,
+            info,
+            metadata,
+            color_type,
+        })
+    }
+}
+
+impl<R: Read> ImageDecoder for JxlImageDecoder<'_, '_, '_, R> {
+    fn dimensions(&self) -> (u32, u32) {
+        (self.info.width, self.info.height)
+    }
+
+    fn color_type(&self) -> ColorType {
+        self.color_type
+    }
+
+    fn icc_profile(&mut self) -> ImageResult<Option<Vec<u8>>> {
+        Ok(self.metadata.icc_profile.clone())
+    }
+
+    fn exif_metadata(&mut self) -> ImageResult<Option<Vec<u8>>> {
+        Ok(self.metadata.boxes.iter().find_map(|b| match b {
+            MetadataBox::Exif(data) => Some(exif_chunk(data).to_vec()),
+            _ => None,
+        }))
+    }
+
+    fn orientation(&mut self) -> ImageResult<Orientation> {
+        Ok(if self.decoder.skip_reorientation == Some(true) {
+            Orientation::from_exif(self.info.orientation as u8).unwrap_or(Orientation::NoTransforms)
+        } else {
+            Orientation::NoTransforms
+        })
+    }
+
+    fn set_limits(&mut self, mut limits: Limits) -> ImageResult<()> {
+        limits.check_support(&LimitSupport::default())?;
+        let (width, height) = self.dimensions();
+        limits.check_dimensions(width, height)?;
+        // The output buffer is allocated by the caller, the rest is left for the decoder
+        limits.reserve(self.total_bytes())?;
+
+        self.decoder.max_width = limits.max_image_width;
+        self.decoder.max_height = limits.max_image_height;
+        self.decoder.max_output_bytes = limits
+            .max_alloc
+            .map(|v| usize::try_from(v).unwrap_or(usize::MAX));
+        Ok(())
+    }
+
+    fn read_image(self, buf: &mut [u8]) -> ImageResult<()> {
+        self.steps
+            .decode_into(&self.decoder, data_type(self.color_type), buf)?;
+        Ok(())
+    }
+
+    fn read_image_boxed(self: Box<Self>, buf: &mut [u8]) -> ImageResult<()> {
+        (*self).read_image(buf)
+    }
+}
+
+/// Color type of the decoded pixels
+fn color_type(info: &ImageInfo) -> ColorType {
+    let gray = info.num_color_channels == 1;
+    let alpha = info.has_alpha_channel;
+    let float = info.exponent_bits_per_sample > 0;
+
+    match (gray, alpha) {
+        (true, false) if !float && info.bits_per_sample <= 8 => ColorType::L8,
+        (true, true) if !float && info.bits_per_sample <= 8 => ColorType::La8,
+        (true, false) => ColorType::L16,
+        (true, true) => ColorType::La16,
+        (false, false) if float => ColorType::Rgb32F,
+        (false, true) if float => ColorType::Rgba32F,
+        (false, false) if info.bits_per_sample <= 8 => ColorType::Rgb8,
+        (false, true) if info.bits_per_sample <= 8 => ColorType::Rgba8,
+        (false, false) if info.bits_per_sample <= 16 => ColorType::Rgb16,
+        (false, true) if info.bits_per_sample <= 16 => ColorType::Rgba16,
+        (false, false) => ColorType::Rgb32F,
+        (false, true) => ColorType::Rgba32F,
+    }
+}
+
+fn data_type(color_type: ColorType) -> JxlDataType {
+    match color_type.bytes_per_pixel() / color_type.channel_count() {
+        1 => JxlDataType::Uint8,
+        2 => JxlDataType::Uint16,
+        _ => JxlDataType::Float,
+    }
+}
+
+/// The Exif box starts with the offset of the TIFF header, which is skipped if valid
+fn exif_chunk(data: &[u8]) -> &[u8] {
+    data.split_first_chunk::<4>()
+        .and_then(|(offset, rest)| rest.get(u32::from_be_bytes(*offset) as usize..))
+        .unwrap_or(data)
+}
+
+impl From<DecodeError> for ImageError {
+    fn from(err: DecodeError) -> Self {
+        match err {
+            DecodeError::Io(err) => ImageError::IoError(err),
+            DecodeError::LimitExceeded { kind, .. } => {
+                ImageError::Limits(LimitError::from_kind(if kind == LimitKind::OutputBytes {
+                    LimitErrorKind::InsufficientMemory
+                } else {
+                    LimitErrorKind::DimensionError
+                }))
+            }
+            err => ImageError::Decoding(DecodingError::new(format_hint(), err)),
+        }
+    }
+}
+
+/// Register [`JxlImageDecoder`] to the [`image`] crate, for the `jxl` extension and the JPEG XL
+/// signatures, so [`image::open`] and [`image::ImageReader`] can read JPEG XL images.
+///
+/// Return `false` if a decoder is already registered for the `jxl` extension.
+#[allow(clippy::must_use_candidate)]
+pub fn register_decoding_hook() -> bool {
+    if !hooks::register_decoding_hook(
+        "jxl".into(),
+        Box::new(|reader| Ok(Box::new(JxlImageDecoder::new(reader)?))),
+    ) {
+        return false;
+    }
+
+    hooks::register_format_detection_hook("jxl".into(), &[0xff, 0x0a], None);
+    hooks::register_format_detection_hook(
+        "jxl".into(),
+        &[
+            0x00, 0x00, 0x00, 0x0c, 0x4a, 0x58, 0x4c, 0x20, 0x0d, 0x0a, 0x87, 0x0a,
+        ],
+        None,
+    );
+    true
+}
+
+/// Extension trait for [`JxlEncoder`]
+pub trait FromDynamic {
+    /// Encode a [`DynamicImage`] to JPEG XL.
+    /// The pixel type, the alpha channel and the grayscale color encoding follow the image,
+    /// and the other settings of the encoder are kept
+    ///
+    /// # Errors
+    /// Return an [`EncodeError`] when internal encoding fails, or
+    /// [`EncodeError::NotSupported`] for color types without a JPEG XL equivalent
+    fn encode_image(&mut self, image: &DynamicImage) -> Result<Vec<u8>, EncodeError>;
+}
+
+impl FromDynamic for JxlEncoder<'_, '_, '_> {
+    fn encode_image(&mut self, image: &DynamicImage) -> Result<Vec<u8>, EncodeError> {
+        encode_bytes(
+            self,
+            image.as_bytes(),
+            image.width(),
+            image.height(),
+            image.color().into(),
+        )
+    }
+}
+
+/// Encoder for the [`image`] crate, writing a JPEG XL image to a writer
+///
+/// # Example
+/// ```
+/// # || -> Result<(), Box<dyn std::error::Error>> {
+/// use image::DynamicImage;
+/// use jpegxl_rs::image::JxlImageEncoder;
+///
+/// let img = DynamicImage::new_rgb8(64, 64);
+/// let mut output = vec![];
+/// img.write_with_encoder(JxlImageEncoder::new(&mut output)?)?;
+/// # Ok(())
+/// # };
+/// ```
+pub struct JxlImageEncoder<'prl, 'mm, 'cms, W> {
+    encoder: JxlEncoder<'prl, 'mm, 'cms>,
+    writer: W,
+    /// Contents of the Exif box
+    exif: Option<Vec<u8>>,
+}
+
+impl<W: Write> JxlImageEncoder<'_, '_, '_, W> {
+    /// Write the image to `writer` with a default encoder
+    ///
+    /// # Errors
+    /// Return [`EncodeError::CannotCreateEncoder`] if it fails to create the encoder
+    pub fn new(writer: W) -> Result<Self, EncodeError> {
+        Ok(JxlImageEncoder::with_encoder(
+            JxlEncoder::builder().build()?,
+            writer,
+        ))
+    }
+}
+
+impl<'prl, 'mm, 'cms, W: Write> JxlImageEncoder<'prl, 'mm, 'cms, W> {
+    /// Write the image to `writer` with the settings of `encoder`.
+    /// See [`FromDynamic::encode_image`] for the settings following the image
+    pub fn with_encoder(encoder: JxlEncoder<'prl, 'mm, 'cms>, writer: W) -> Self {
+        Self {
+            encoder,
+            writer,
+            exif: None,
+        }
+    }
+}
+
+impl<W: Write> ImageEncoder for JxlImageEncoder<'_, '_, '_, W> {
+    fn write_image(
+        mut self,
+        buf: &[u8],
+        width: u32,
+        height: u32,
+        color_type: ExtendedColorType,
+    ) -> ImageResult<()> {
+        if pixel_layout(color_type).is_none() {
+            return Err(ImageError::Unsupported(
+                UnsupportedError::from_format_and_kind(
+                    format_hint(),
+                    UnsupportedErrorKind::Color(color_type),
+                ),
+            ));
+        }
+
+        if let Some(exif) = self.exif.take() {
+            self.encoder
+                .add_metadata(&EncoderMetadata::Exif(&exif), true)?;
+        }
+        let data = encode_bytes(&mut self.encoder, buf, width, height, color_type)?;
+        self.writer.write_all(&data)?;
+
+        Ok(())
+    }
+
+    fn set_icc_profile(&mut self, icc_profile: Vec<u8>) -> Result<(), UnsupportedError> {
+        self.encoder.icc_profile = Some(icc_profile);
+        Ok(())
+    }
+
+    fn set_exif_metadata(&mut self, exif: Vec<u8>) -> Result<(), UnsupportedError> {
+        // The TIFF header follows the offset immediately
+        self.exif = Some([&[0; 4], &exif[..]].concat());
+        Ok(())
+    }
+}
+
+/// Number of channels and data type of the pixels of a color type
+fn pixel_layout(color_type: ExtendedColorType) -> Option<(u32, JxlDataType)> {
+    use ExtendedColorType as C;
+
+    Some(match color_type {
+        C::L8 => (1, JxlDataType::Uint8),
+        C::La8 => (2, JxlDataType::Uint8),
+        C::Rgb8 => (3, JxlDataType::Uint8),
+        C::Rgba8 => (4, JxlDataType::Uint8),
+        C::L16 => (1, JxlDataType::Uint16),
+        C::La16 => (2, JxlDataType::Uint16),
+        C::Rgb16 => (3, JxlDataType::Uint16),
+        C::Rgba16 => (4, JxlDataType::Uint16),
+        C::Rgb32F => (3, JxlDataType::Float),
+        C::Rgba32F => (4, JxlDataType::Float),
+        _ => return None,
+    })
+}
+
+/// Encode native endian pixels, with the alpha channel and the grayscale color encoding of
+/// the encoder set for `color_type` during the encoding
+fn encode_bytes(
+    encoder: &mut JxlEncoder,
+    buf: &[u8],
+    width: u32,
+    height: u32,
+    color_type: ExtendedColorType,
+) -> Result<Vec<u8>, EncodeError> {
+    let (num_channels, data_type) = pixel_layout(color_type).ok_or(EncodeError::NotSupported)?;
+
+    let has_alpha = std::mem::replace(&mut encoder.has_alpha, num_channels % 2 == 0);
+    let color_encoding = encoder.color_encoding.clone();
+    match (num_channels < 3, &encoder.color_encoding) {
+        (true, None) => encoder.color_encoding = Some(ColorEncoding::SrgbLuma),
+        (false, Some(ColorEncoding::SrgbLuma | ColorEncoding::LinearSrgbLuma)) => {
+            encoder.color_encoding = None;
+        }
+        _ => {}
+    }
+
+    let pixel_format = JxlPixelFormat {
+        num_channels,
+        data_type,
+        endianness: Endianness::Native,
+        align: 0,
+    };
+    let res = match data_type {
+        JxlDataType::Uint8 => encode_as::<u8>(encoder, buf, &pixel_format, width, height),
+        JxlDataType::Uint16 => encode_as::<u16>(encoder, buf, &pixel_format, width, height),
+        _ => encode_as::<f32>(encoder, buf, &pixel_format, width, height),
+    };
+
+    encoder.has_alpha = has_alpha;
+    encoder.color_encoding = color_encoding;
+    res
+}
+
+fn encode_as<T: PixelType>(
+    encoder: &mut JxlEncoder,
+    buf: &[u8],
+    pixel_format: &JxlPixelFormat,
+    width: u32,
+    height: u32,
+) -> Result<Vec<u8>, EncodeError> {
+    // The buffer may not be aligned for `T`
+    let data = T::convert(buf, pixel_format);
+    let frame = EncoderFrame::new(&data).num_channels(pixel_format.num_channels);
+    Ok(encoder.encode_frame::<T, T>(&frame, width, height)?.data)
+}
+
+fn format_hint() -> ImageFormatHint {
+    ImageFormatHint::Name("JPEG XL".to_string())
+}
+
+impl From<EncodeError> for ImageError {
+    fn from(err: EncodeError) -> Self {
+        ImageError::Encoding(EncodingError::new(format_hint(), err))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -171,6 +609,26 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    #[cfg_attr(coverage_nightly, coverage(off))]
+    fn reader() -> TestResult {
+        let decoder = decoder_builder().build()?;
+
+        let img = decoder
+            .decode_reader_to_image(SAMPLE_JXL)?
+            .expect("Failed to create DynamicImage");
+        let sample_png = image::load_from_memory_with_format(SAMPLE_PNG, image::ImageFormat::Png)?;
+        assert_eq!(img.to_rgba16(), sample_png.to_rgba16());
+
+        assert!(
+            decoder
+                .decode_reader_to_image_with::<f16, _>(SAMPLE_JXL)?
+                .is_none()
+        );
+
+        Ok(())
+    }
+
     #[test]
     #[cfg_attr(coverage_nightly, coverage(off))]
     fn pixel_type() -> TestResult {
@@ -281,4 +739,181 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    #[cfg_attr(coverage_nightly, coverage(off))]
+    fn image_decoder() -> TestResult {
+        let sample_png = image::load_from_memory_with_format(SAMPLE_PNG, image::ImageFormat::Png)?;
+
+        let decoder = JxlImageDecoder::new(SAMPLE_JXL)?;
+        assert_eq!(
+            decoder.dimensions(),
+            (sample_png.width(), sample_png.height())
+        );
+        assert_eq!(decoder.color_type(), ColorType::Rgba16);
+        let img = DynamicImage::from_decoder(decoder)?;
+        assert_eq!(img.to_rgba16(), sample_png.to_rgba16());
+
+        // Metadata before the pixels
+        let mut decoder = JxlImageDecoder::new(SAMPLE_JXL)?;
+        decoder.icc_profile()?;
+        decoder.exif_metadata()?;
+        assert_eq!(decoder.orientation()?, Orientation::NoTransforms);
+        let img = DynamicImage::from_decoder(decoder)?;
+        assert_eq!(img.to_rgba16(), sample_png.to_rgba16());
+
+        let decoder = JxlImageDecoder::new(SAMPLE_JXL_GRAY)?;
+        assert_eq!(decoder.color_type(), ColorType::L8);
+
+        let mut decoder = JxlImageDecoder::with_decoder(
+            decoder_builder().skip_reorientation(true).build()?,
+            SAMPLE_JXL,
+        )?;
+        assert_eq!(
+            Some(decoder.orientation()?),
+            Orientation::from_exif(decoder.info.orientation as u8)
+        );
+
+        let mut decoder = JxlImageDecoder::new(SAMPLE_JXL)?;
+        let limits = {
+            let mut limits = image::Limits::default();
+            limits.max_alloc = Some(1024);
+            limits
+        };
+        assert!(matches!(
+            decoder.set_limits(limits),
+            Err(ImageError::Limits(_))
+        ));
+
+        let mut decoder = JxlImageDecoder::new(SAMPLE_JXL)?;
+        let limits = {
+            let mut limits = image::Limits::default();
+            limits.max_alloc = Some(decoder.total_bytes());
+            limits
+        };
+        decoder.set_limits(limits)?;
+        let img = DynamicImage::from_decoder(decoder)?;
+        assert_eq!(img.to_rgba16(), sample_png.to_rgba16());
+
+        assert!(JxlImageDecoder::new(&[0u8; 64][..]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg_attr(coverage_nightly, coverage(off))]
+    fn read_on_demand() -> TestResult {
+        let sample = include_bytes!("../../samples/bench.jxl");
+
+        // Only the header is read for the metadata
+        let mut rest = &sample[..];
+        let mut decoder = JxlImageDecoder::new(&mut rest)?;
+        decoder.icc_profile()?;
+        decoder.exif_metadata()?;
+        let (width, height) = decoder.dimensions();
+        drop(decoder);
+        assert!(!rest.is_empty());
+
+        let img = DynamicImage::from_decoder(JxlImageDecoder::new(&sample[..])?)?;
+        assert_eq!((img.width(), img.height()), (width, height));
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg_attr(coverage_nightly, coverage(off))]
+    fn exif() -> TestResult {
+        use crate::{
+            encode::{EncoderResult, Metadata as EncoderMetadata},
+            encoder_builder,
+            tests::SAMPLE_EXIF,
+        };
+
+        let sample =
+            image::load_from_memory_with_format(SAMPLE_PNG, image::ImageFormat::Png)?.to_rgb8();
+        let exif = [&[0; 4], SAMPLE_EXIF].concat();
+        let mut encoder = encoder_builder().build()?;
+        encoder.add_metadata(&EncoderMetadata::Exif(&exif), false)?;
+        let result: EncoderResult<u8> =
+            encoder.encode(sample.as_raw(), sample.width(), sample.height())?;
+
+        let mut decoder = JxlImageDecoder::new(&*result)?;
+        assert_eq!(decoder.exif_metadata()?.as_deref(), Some(SAMPLE_EXIF));
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg_attr(coverage_nightly, coverage(off))]
+    fn decoding_hook() -> TestResult {
+        assert!(register_decoding_hook());
+        assert!(!register_decoding_hook());
+
+        let img = image::ImageReader::new(std::io::Cursor::new(SAMPLE_JXL))
+            .with_guessed_format()?
+            .decode()?;
+        let sample_png = image::load_from_memory_with_format(SAMPLE_PNG, image::ImageFormat::Png)?;
+        assert_eq!(img.to_rgba16(), sample_png.to_rgba16());
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg_attr(coverage_nightly, coverage(off))]
+    fn image_encoder() -> TestResult {
+        use crate::{encoder_builder, tests::SAMPLE_EXIF};
+
+        let sample = image::load_from_memory_with_format(SAMPLE_PNG, image::ImageFormat::Png)?;
+
+        for img in [
+            DynamicImage::ImageLuma8(sample.to_luma8()),
+            DynamicImage::ImageLumaA8(sample.to_luma_alpha8()),
+            DynamicImage::ImageRgb8(sample.to_rgb8()),
+            DynamicImage::ImageRgba8(sample.to_rgba8()),
+            DynamicImage::ImageLuma16(sample.to_luma16()),
+            DynamicImage::ImageRgba16(sample.to_rgba16()),
+        ] {
+            let encoder = encoder_builder()
+                .lossless(true)
+                .uses_original_profile(true)
+                .build()?;
+            let mut output = vec![];
+            img.write_with_encoder(JxlImageEncoder::with_encoder(encoder, &mut output))?;
+
+            let parsed = JxlImageDecoder::new(&*output)?;
+            assert_eq!(parsed.color_type(), img.color());
+            assert_eq!(DynamicImage::from_decoder(parsed)?, img);
+        }
+
+        let mut encoder = encoder_builder().build()?;
+        let data = encoder.encode_image(&DynamicImage::ImageRgb32F(sample.to_rgb32f()))?;
+        let (info, _) = decoder_builder().build()?.decode(&data)?;
+        assert_eq!(info.width, sample.width());
+        assert!(!encoder.has_alpha);
+        assert!(encoder.color_encoding.is_none());
+
+        // Metadata carried over
+        let mut output = vec![];
+        let mut image_encoder = JxlImageEncoder::new(&mut output)?;
+        image_encoder.set_exif_metadata(SAMPLE_EXIF.to_vec())?;
+        sample.write_with_encoder(image_encoder)?;
+        let mut parsed = JxlImageDecoder::new(&*output)?;
+        assert_eq!(parsed.exif_metadata()?.as_deref(), Some(SAMPLE_EXIF));
+
+        let icc = JxlImageDecoder::new(SAMPLE_JXL)?
+            .icc_profile()?
+            .expect("ICC profile not found");
+        let mut output = vec![];
+        let mut image_encoder = JxlImageEncoder::new(&mut output)?;
+        image_encoder.set_icc_profile(icc.clone())?;
+        sample.write_with_encoder(image_encoder)?;
+        assert!(JxlImageDecoder::new(&*output)?.icc_profile()?.is_some());
+
+        assert!(matches!(
+            JxlImageEncoder::new(vec![])?.write_image(&[0; 4], 1, 1, ExtendedColorType::Bgr8),
+            Err(ImageError::Unsupported(_))
+        ));
+
+        Ok(())
+    }
 }
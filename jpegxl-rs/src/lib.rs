@@ -19,10 +19,12 @@ along with jpegxl-rs.  If not, see <https://www.gnu.org/licenses/>.
 #![cfg_attr(coverage_nightly, feature(coverage_attribute))]
 #![doc = include_str!("../README.md")]
 
+pub mod cms;
 mod common;
 pub mod decode;
 pub mod encode;
 mod errors;
+pub mod gain_map;
 pub mod memory;
 pub mod parallel;
 pub mod utils;
@@ -33,10 +35,10 @@ pub mod image;
 #[cfg(test)]
 mod tests;
 
-pub use common::Endianness;
+pub use common::{CancellationToken, Endianness};
 pub use decode::decoder_builder;
 pub use encode::encoder_builder;
-pub use errors::{DecodeError, EncodeError};
+pub use errors::{DecodeError, EncodeError, LimitKind};
 
 pub use parallel::resizable_runner::ResizableRunner;
 pub use parallel::threads_runner::ThreadsRunner;
@@ -1,8 +1,35 @@
+use image::DynamicImage;
+
+use crate::{
+    EncodeError,
+    common::PixelType,
+    encode::{EncoderFrame, JxlEncoder},
+};
+
 mod decode;
 mod encode;
 
 pub const SAMPLE_PNG: &[u8] = include_bytes!("../../samples/sample.png");
 
+pub fn get_sample() -> DynamicImage {
+    image::load_from_memory_with_format(SAMPLE_PNG, image::ImageFormat::Png)
+        .expect("Failed to get sample file")
+}
+
+/// Encode `frames` frames of the sample in RGB with the settings of `encoder`
+pub fn encode_frames<U: PixelType>(
+    frames: usize,
+    encoder: &mut JxlEncoder,
+) -> Result<Vec<u8>, EncodeError> {
+    let sample = get_sample().to_rgb8();
+    let frame = EncoderFrame::new(sample.as_raw());
+    let mut multiple = encoder.multiple::<U>(sample.width(), sample.height())?;
+    for _ in 0..frames {
+        multiple = multiple.add_frame(&frame)?;
+    }
+    Ok(multiple.encode()?.data)
+}
+
 /// Compile-time assertion that [`JxlDecoder`] is Send
 const _: () = {
     const fn assert_send<T: Send>() {}
@@ -15,7 +42,7 @@ const _: () = {
     assert_send::<crate::encode::JxlEncoder>();
 };
 const SAMPLE_JPEG: &[u8] = include_bytes!("../../samples/sample.jpg");
-const SAMPLE_EXIF: &[u8] = include_bytes!("../../samples/sample.exif");
+pub const SAMPLE_EXIF: &[u8] = include_bytes!("../../samples/sample.exif");
 const SAMPLE_XMP: &[u8] = include_bytes!("../../samples/sample.xmp");
 pub const SAMPLE_JXL: &[u8] = include_bytes!("../../samples/sample.jxl");
 const SAMPLE_JXL_JPEG: &[u8] = include_bytes!("../../samples/sample_jpg.jxl");
@@ -23,7 +23,7 @@ use pretty_assertions::assert_eq;
 use testresult::TestResult;
 
 use crate::{
-    DecodeError,
+    DecodeError, LimitKind,
     common::Endianness,
     decode::{Data, Metadata, PixelFormat, Pixels},
     decoder_builder,
@@ -81,6 +81,31 @@ fn sample_2bit() -> TestResult {
     Ok(())
 }
 
+#[test]
+fn bit_depth() -> TestResult {
+    use crate::decode::BitDepth;
+
+    let decoder = decoder_builder()
+        .bit_depth(BitDepth::FromCodestream)
+        .build()?;
+    let (_, data) = decoder.decode_with::<u8>(super::SAMPLE_JXL_2BIT)?;
+    assert!(data.iter().all(|&v| v <= 3));
+
+    let decoder = decoder_builder().bit_depth(BitDepth::Custom(10)).build()?;
+    let (_, data) = decoder.decode_with::<u16>(super::SAMPLE_JXL)?;
+    assert!(data.iter().all(|&v| v <= 1023));
+
+    // Float samples are not affected
+    decoder.decode_with::<f32>(super::SAMPLE_JXL)?;
+
+    assert!(matches!(
+        decoder.decode_with::<u8>(super::SAMPLE_JXL),
+        Err(DecodeError::GenericError)
+    ));
+
+    Ok(())
+}
+
 #[test]
 fn sample_gray() -> TestResult {
     let decoder = decoder_builder().build()?;
@@ -356,3 +381,799 @@ fn decode_with_icc() -> TestResult {
 
     Ok(())
 }
+
+#[test]
+fn streaming() -> TestResult {
+    use crate::decode::Event;
+
+    let mut decoder = decoder_builder().icc_profile(true).build()?;
+    let (_, Pixels::Uint16(expected)) = decoder.decode(super::SAMPLE_JXL)? else {
+        panic!("Expected Uint16 pixels");
+    };
+
+    let mut stream = decoder.stream()?;
+    let mut chunks = super::SAMPLE_JXL.chunks(100);
+    let mut basic_info = None;
+    let mut pixels = None;
+    let metadata = loop {
+        match stream.process()? {
+            Event::NeedMoreInput => match chunks.next() {
+                Some(chunk) => stream.feed(chunk)?,
+                None => stream.close(),
+            },
+            Event::BasicInfo(info) => basic_info = Some(info),
+            Event::ColorEncoding(icc) => assert!(icc.is_some()),
+            Event::FullImage(p) => pixels = Some(p),
+            Event::Complete(metadata) => break metadata,
+            _ => {}
+        }
+    };
+
+    let basic_info = basic_info.expect("Basic info not reported");
+    assert_eq!(basic_info.xsize, metadata.width);
+    assert_eq!(basic_info.ysize, metadata.height);
+    assert!(metadata.icc_profile.is_some());
+    let Some(Pixels::Uint16(pixels)) = pixels else {
+        panic!("Expected Uint16 pixels");
+    };
+    assert_eq!(pixels, expected);
+
+    Ok(())
+}
+
+#[test]
+fn streaming_early_stop() -> TestResult {
+    use crate::decode::Event;
+
+    let mut decoder = decoder_builder().build()?;
+
+    {
+        let mut stream = decoder.stream_with::<u8>()?;
+        stream.feed(&super::SAMPLE_JXL[..200])?;
+        loop {
+            match stream.process()? {
+                Event::BasicInfo(info) => {
+                    assert!(info.xsize > 0);
+                    break;
+                }
+                Event::NeedMoreInput => panic!("Basic info should fit in the first chunk"),
+                _ => {}
+            }
+        }
+        stream.close();
+        assert!(matches!(stream.feed(&[0]), Err(DecodeError::InputClosed)));
+    }
+
+    // The decoder is reusable after a stream is dropped
+    decoder.decode(super::SAMPLE_JXL)?;
+
+    Ok(())
+}
+
+#[test]
+fn reader() -> TestResult {
+    let decoder = decoder_builder().icc_profile(true).build()?;
+
+    let (expected_meta, expected) = decoder.decode_with::<u8>(super::SAMPLE_JXL)?;
+    let (meta, data) = decoder.decode_reader_with::<u8, _>(Cursor::new(super::SAMPLE_JXL))?;
+    assert_eq!(meta.width, expected_meta.width);
+    assert_eq!(meta.height, expected_meta.height);
+    assert_eq!(meta.icc_profile, expected_meta.icc_profile);
+    assert_eq!(data, expected);
+
+    let (_, data) = decoder.decode_reader(std::io::BufReader::new(super::SAMPLE_JXL))?;
+    assert!(matches!(data, Pixels::Uint16(_)));
+
+    let (_, data) = decoder.reconstruct_reader(super::SAMPLE_JXL_JPEG)?;
+    assert!(matches!(data, Data::Jpeg(_)));
+
+    assert!(matches!(
+        decoder.decode_reader(&[0u8; 64][..]),
+        Err(DecodeError::InvalidInput)
+    ));
+    assert!(matches!(
+        decoder.decode_reader(&super::SAMPLE_JXL[..500]),
+        Err(DecodeError::GenericError)
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn probe() -> TestResult {
+    let decoder = decoder_builder().build()?;
+    let (meta, _) = decoder.decode(super::SAMPLE_JXL)?;
+
+    let info = decoder.probe(super::SAMPLE_JXL)?;
+    assert_eq!(info.width, meta.width);
+    assert_eq!(info.height, meta.height);
+    assert_eq!(info.bits_per_sample, 16);
+    assert_eq!(info.has_alpha_channel, meta.has_alpha_channel);
+    assert_eq!(info.extra_channels, meta.extra_channels);
+    assert!(info.preview.is_none());
+
+    // The header is at the start of the file
+    let info = decoder.probe(&super::SAMPLE_JXL[..2048])?;
+    assert_eq!(info.width, meta.width);
+
+    let info = decoder.probe(super::SAMPLE_JXL_GRAY)?;
+    assert_eq!(info.num_color_channels, 1);
+
+    assert!(matches!(
+        decoder.probe(&super::SAMPLE_JXL[..8]),
+        Err(DecodeError::GenericError)
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn decode_into() -> TestResult {
+    let decoder = decoder_builder().build()?;
+    let (meta, expected) = decoder.decode_with::<u16>(super::SAMPLE_JXL)?;
+    let row = meta.width as usize * 4;
+
+    let mut buffer = vec![0u16; expected.len()];
+    let meta = decoder.decode_into(super::SAMPLE_JXL, &mut buffer, None)?;
+    assert_eq!(buffer, expected);
+
+    // Padded rows
+    let stride = row + 3;
+    let mut buffer = vec![0u16; stride * meta.height as usize];
+    decoder.decode_reader_into(super::SAMPLE_JXL, &mut buffer, Some(stride))?;
+    for (actual, expected) in buffer.chunks_exact(stride).zip(expected.chunks_exact(row)) {
+        assert_eq!(&actual[..row], expected);
+    }
+
+    assert!(matches!(
+        decoder.decode_into(super::SAMPLE_JXL, &mut buffer, Some(row - 1)),
+        Err(DecodeError::InvalidStride { .. })
+    ));
+    assert!(matches!(
+        decoder.decode_into(super::SAMPLE_JXL, &mut buffer[..row], None),
+        Err(DecodeError::BufferTooSmall { .. })
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn decode_into_layers() -> TestResult {
+    let sample = super::get_sample().to_rgb8();
+    let result =
+        super::encode_frames::<u8>(2, &mut crate::encoder_builder().lossless(true).build()?)?;
+
+    // Each layer is written to the buffer with the stride
+    let decoder = decoder_builder().coalescing(false).build()?;
+    let row = sample.width() as usize * 3;
+    let stride = row + 5;
+    let mut buffer = vec![0u8; stride * sample.height() as usize];
+    decoder.decode_into(&result, &mut buffer, Some(stride))?;
+    for (actual, expected) in buffer
+        .chunks_exact(stride)
+        .zip(sample.as_raw().chunks_exact(row))
+    {
+        assert_eq!(&actual[..row], expected);
+    }
+
+    assert!(matches!(
+        decoder.decode_into(&result, &mut buffer, Some(row - 1)),
+        Err(DecodeError::InvalidStride { .. })
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn rows() -> TestResult {
+    use std::sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    use crate::decode::RowCallback;
+
+    struct Count {
+        inits: usize,
+        pixels: usize,
+    }
+
+    impl RowCallback<f32> for Count {
+        type State = (usize, Mutex<Vec<usize>>);
+
+        fn init(&mut self, num_threads: usize, _pixels_per_thread: usize) -> Self::State {
+            self.inits += 1;
+            (num_threads, Mutex::new(vec![0; num_threads]))
+        }
+
+        fn run(&self, state: &Self::State, thread_id: usize, _x: usize, _y: usize, pixels: &[f32]) {
+            assert!(thread_id < state.0);
+            state.1.lock().expect("Failed to lock")[thread_id] += pixels.len() / 4;
+        }
+
+        fn destroy(&mut self, state: Self::State) {
+            self.pixels += state
+                .1
+                .into_inner()
+                .expect("Failed to lock")
+                .iter()
+                .sum::<usize>();
+        }
+    }
+
+    let threads_runner = ThreadsRunner::default();
+    let decoder = decoder_builder().parallel_runner(&threads_runner).build()?;
+    let (meta, expected) = decoder.decode_with::<f32>(super::SAMPLE_JXL)?;
+    let row = meta.width as usize * 4;
+
+    let mut image = vec![0.0; expected.len()];
+    decoder.decode_rows::<f32>(super::SAMPLE_JXL, |x, y, pixels| {
+        let start = y * row + x * 4;
+        image[start..start + pixels.len()].copy_from_slice(pixels);
+    })?;
+    assert_eq!(image, expected);
+
+    let mut count = Count {
+        inits: 0,
+        pixels: 0,
+    };
+    let meta = decoder.decode_rows_parallel(super::SAMPLE_JXL, &mut count)?;
+    assert_eq!(count.inits, 1);
+    assert_eq!(count.pixels, (meta.width * meta.height) as usize);
+
+    let calls = AtomicUsize::new(0);
+    decoder_builder()
+        .build()?
+        .decode_rows::<u8>(super::SAMPLE_JXL_GRAY, |_, _, _| {
+            calls.fetch_add(1, Ordering::Relaxed);
+        })?;
+    assert!(calls.load(Ordering::Relaxed) > 0);
+
+    Ok(())
+}
+
+#[test]
+fn progressive() -> TestResult {
+    use crate::decode::ProgressiveDetail;
+
+    let decoder = decoder_builder()
+        .progressive_detail(ProgressiveDetail::Passes)
+        .build()?;
+    let (_, expected) = decoder.decode_with::<u8>(super::SAMPLE_JXL)?;
+
+    let mut ratios = vec![];
+    let (Metadata { width, height, .. }, data) =
+        decoder.decode_progressive::<u8>(super::SAMPLE_JXL, |pixels, ratio| {
+            assert_eq!(pixels.len(), expected.len());
+            ratios.push(ratio);
+        })?;
+    assert_eq!(data.len(), (width * height * 4) as usize);
+    assert_eq!(data, expected);
+    assert!(ratios.iter().all(|r| [1, 2, 4, 8].contains(r)));
+
+    // Truncated input is rendered as far as possible
+    let mut rendered = false;
+    let res = decoder.decode_progressive::<u8>(
+        &super::SAMPLE_JXL[..super::SAMPLE_JXL.len() / 2],
+        |pixels, _| {
+            assert_eq!(pixels.len(), expected.len());
+            rendered = true;
+        },
+    );
+    assert!(matches!(res, Err(DecodeError::GenericError)));
+    assert!(rendered);
+
+    Ok(())
+}
+
+#[test]
+fn streaming_flush() -> TestResult {
+    use crate::decode::Event;
+
+    let mut decoder = decoder_builder().build()?;
+    let mut stream = decoder.stream_with::<u8>()?;
+    stream.feed(&super::SAMPLE_JXL[..super::SAMPLE_JXL.len() / 2])?;
+
+    let partial = loop {
+        match stream.process()? {
+            Event::NeedMoreInput => break stream.flush(),
+            Event::FullImage(_) | Event::Complete(_) => panic!("Input is truncated"),
+            _ => {}
+        }
+    };
+    let Some(Pixels::Uint8(partial)) = partial else {
+        panic!("Expected partial Uint8 pixels");
+    };
+    assert!(!partial.is_empty());
+    assert!([1, 2, 4, 8].contains(&stream.downsampling_ratio()));
+
+    Ok(())
+}
+
+#[test]
+fn frames() -> TestResult {
+    let mut decoder = decoder_builder().build()?;
+    let (metadata, expected) = decoder.decode(super::SAMPLE_JXL)?;
+    assert!(metadata.animation.is_none());
+
+    let mut frames = decoder.frames(super::SAMPLE_JXL)?;
+    let (info, pixels) = frames.next().expect("No frame decoded")?;
+    assert!(info.is_last);
+    assert_eq!(info.duration, 0);
+    assert_eq!(info.name, "");
+    let (Pixels::Uint16(pixels), Pixels::Uint16(expected)) = (pixels, expected) else {
+        panic!("Expected Uint16 pixels");
+    };
+    assert_eq!(pixels, expected);
+    assert!(frames.basic_info().is_some());
+    assert!(frames.next().is_none());
+    drop(frames);
+
+    assert!(matches!(
+        decoder.frames(&[0x00, 0x00]),
+        Err(DecodeError::InvalidInput)
+    ));
+    let mut frames = decoder.frames(&super::SAMPLE_JXL[..500])?;
+    assert!(matches!(
+        frames.next(),
+        Some(Err(DecodeError::GenericError))
+    ));
+    assert!(frames.next().is_none());
+
+    Ok(())
+}
+
+#[test]
+fn seek_frames() -> TestResult {
+    use crate::decode::Event;
+
+    let mut decoder = decoder_builder().build()?;
+    let (_, expected) = decoder.decode_with::<u16>(super::SAMPLE_JXL)?;
+
+    let mut frames = decoder.frames(super::SAMPLE_JXL)?;
+    assert!(frames.nth(1).is_none());
+
+    // Decode again without re-creating the decoder
+    let (info, Pixels::Uint16(pixels)) = frames.frame(0).expect("No frame decoded")? else {
+        panic!("Expected Uint16 pixels");
+    };
+    assert!(info.is_last);
+    assert_eq!(pixels, expected);
+    assert!(frames.next().is_none());
+
+    frames.rewind();
+    assert_eq!(frames.by_ref().step_by(2).count(), 1);
+    assert!(frames.frame(1).is_none());
+    drop(frames);
+
+    let mut stream = decoder.stream()?;
+    assert!(stream.skip_current_frame().is_err());
+    stream.feed(super::SAMPLE_JXL)?;
+    stream.close();
+    loop {
+        match stream.process()? {
+            Event::BasicInfo(_) => stream.skip_frames(1),
+            Event::Frame(_) | Event::FullImage(_) => panic!("Frame not skipped"),
+            Event::Complete(_) => break,
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn layers() -> TestResult {
+    use crate::decode::BlendMode;
+
+    let sample = super::get_sample().to_rgb8();
+    let result = super::encode_frames::<u8>(2, &mut crate::encoder_builder().build()?)?;
+
+    let mut decoder = decoder_builder().build()?;
+    assert_eq!(decoder.frames(&result)?.count(), 1);
+
+    let layers = decoder.layers(&result)?.collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(layers.len(), 2);
+    for (info, pixels) in &layers {
+        assert_eq!(info.layer.xsize, sample.width());
+        assert_eq!(info.layer.ysize, sample.height());
+        assert_eq!(info.layer.blend_info.blendmode, BlendMode::Replace);
+        let Pixels::Uint8(pixels) = pixels else {
+            panic!("Expected Uint8 pixels");
+        };
+        assert_eq!(
+            pixels.len(),
+            (sample.width() * sample.height() * 3) as usize
+        );
+    }
+    assert!(layers[1].0.is_last);
+
+    // The coalescing option is left untouched
+    assert!(decoder.coalescing.is_none());
+
+    Ok(())
+}
+
+#[test]
+fn preview() -> TestResult {
+    let decoder = decoder_builder().build()?;
+
+    // The sample has no preview, so decoding stops after the basic info
+    assert!(decoder.decode_preview(super::SAMPLE_JXL)?.is_none());
+    assert!(
+        decoder
+            .decode_preview_with::<u8>(super::SAMPLE_JXL)?
+            .is_none()
+    );
+    assert!(matches!(
+        decoder.decode_preview(&[0x00, 0x00]),
+        Err(DecodeError::InvalidInput)
+    ));
+
+    decoder.decode(super::SAMPLE_JXL)?;
+
+    Ok(())
+}
+
+#[test]
+fn metadata_boxes() -> TestResult {
+    use crate::{decode::MetadataBox, encode::Metadata as EncoderMetadata};
+
+    let mut encoder = crate::encoder_builder().build()?;
+    encoder.add_metadata(&EncoderMetadata::Exif(super::SAMPLE_EXIF), true)?;
+    encoder.add_metadata(&EncoderMetadata::Xmp(super::SAMPLE_XMP), false)?;
+    let result = super::encode_frames::<u8>(1, &mut encoder)?;
+
+    // Not collected by default
+    let decoder = decoder_builder().build()?;
+    let (Metadata { boxes, .. }, _) = decoder.decode(&result)?;
+    assert!(boxes.is_empty());
+
+    let decoder = decoder_builder().metadata_boxes(true).build()?;
+    let (Metadata { boxes, .. }, _) = decoder.decode(&result)?;
+    assert_eq!(
+        boxes,
+        vec![
+            MetadataBox::Exif(super::SAMPLE_EXIF.to_vec()),
+            MetadataBox::Xmp(super::SAMPLE_XMP.to_vec())
+        ]
+    );
+
+    let decoder = decoder_builder()
+        .metadata_boxes(true)
+        .decompress(false)
+        .build()?;
+    let (Metadata { boxes, .. }, _) = decoder.decode(&result)?;
+    assert!(matches!(boxes[0], MetadataBox::Custom(t, _) if &t == b"brob"));
+    assert_eq!(boxes[1], MetadataBox::Xmp(super::SAMPLE_XMP.to_vec()));
+
+    Ok(())
+}
+
+#[test]
+fn gain_map() -> TestResult {
+    use crate::{encode::EncoderResult, gain_map::GainMap};
+
+    let (width, height) = super::get_sample().to_rgb8().dimensions();
+
+    // The gain map is a bare codestream of a smaller image
+    let gray = vec![128u8; (width / 4 * (height / 4)) as usize];
+    let mut encoder = crate::encoder_builder()
+        .color_encoding(crate::encode::ColorEncoding::SrgbLuma)
+        .build()?;
+    let codestream: EncoderResult<u8> = encoder.encode(&gray, width / 4, height / 4)?;
+
+    let gain_map = GainMap {
+        metadata: vec![0, 1, 2, 3],
+        gain_map: codestream.data,
+        ..Default::default()
+    };
+    let mut encoder = crate::encoder_builder().build()?;
+    encoder.add_gain_map(&gain_map)?;
+    let result = super::encode_frames::<u8>(1, &mut encoder)?;
+
+    let decoder = decoder_builder().build()?;
+    let (metadata, _) = decoder.decode(&result)?;
+    assert!(metadata.gain_map()?.is_none());
+
+    let decoder = decoder_builder().metadata_boxes(true).build()?;
+    let (metadata, _) = decoder.decode(&result)?;
+    let parsed = metadata.gain_map()?.ok_or("no gain map")?;
+    assert_eq!(parsed.metadata, gain_map.metadata);
+    assert!(parsed.color_encoding.is_none());
+    assert_eq!(parsed.gain_map, gain_map.gain_map);
+
+    // The gain map itself can be decoded
+    let (info, _) = decoder.decode_with::<u8>(&parsed.gain_map)?;
+    assert_eq!(info.width, width / 4);
+
+    Ok(())
+}
+
+#[test]
+fn extra_channels() -> TestResult {
+    use crate::decode::ExtraChannelType;
+
+    let decoder = decoder_builder().build()?;
+
+    let (metadata, pixels, planes) = decoder.decode_extra_channels_with::<u8>(super::SAMPLE_JXL)?;
+    assert_eq!(metadata.extra_channels.len(), 1);
+    let alpha = &metadata.extra_channels[0];
+    assert_eq!(alpha.channel_type, ExtraChannelType::Alpha);
+    assert_eq!(alpha.dim_shift, 0);
+    assert!(alpha.bits_per_sample > 0);
+
+    // The alpha plane matches the interleaved alpha channel
+    assert_eq!(planes.len(), 1);
+    let interleaved = pixels
+        .iter()
+        .skip(3)
+        .step_by(4)
+        .copied()
+        .collect::<Vec<_>>();
+    assert_eq!(planes[0], interleaved);
+
+    let (_, _, planes) = decoder.decode_extra_channels(super::SAMPLE_JXL)?;
+    assert!(matches!(planes[..], [Pixels::Uint16(_)]));
+
+    let (metadata, _, planes) = decoder.decode_extra_channels(super::SAMPLE_JXL_GRAY)?;
+    assert_eq!(metadata.extra_channels.len(), planes.len());
+
+    Ok(())
+}
+
+#[test]
+fn color_encoding() -> TestResult {
+    use crate::{
+        decode::{ColorSpace, Primaries, TransferFunction, WhitePoint},
+        encode::ColorEncoding,
+    };
+
+    let mut encoder = crate::encoder_builder()
+        .color_encoding(ColorEncoding::Rec2100Pq)
+        .build()?;
+    let result = super::encode_frames::<u16>(1, &mut encoder)?;
+
+    let decoder = decoder_builder().build()?;
+    let (meta, _) = decoder.decode(&result)?;
+    let original = meta
+        .original_color_encoding
+        .expect("Color encoding not retrieved");
+    assert_eq!(original.color_space, ColorSpace::Rgb);
+    assert_eq!(original.white_point, WhitePoint::D65);
+    assert_eq!(original.primaries, Some(Primaries::Rec2100));
+    assert_eq!(original.transfer_function, TransferFunction::Pq);
+    assert!(meta.data_color_encoding.is_some());
+
+    let decoder = decoder_builder()
+        .preferred_color_profile(ColorEncoding::Srgb)
+        .build()?;
+    let (meta, _) = decoder.decode(&result)?;
+    let data = meta
+        .data_color_encoding
+        .expect("Color encoding not retrieved");
+    assert_eq!(data.transfer_function, TransferFunction::Srgb);
+
+    assert_eq!(
+        decoder.probe(&result)?.color_encoding,
+        meta.original_color_encoding
+    );
+
+    Ok(())
+}
+
+#[test]
+fn output_color_profile() -> TestResult {
+    use crate::{decode::ColorProfile, encode::ColorEncoding};
+
+    let decoder = decoder_builder()
+        .icc_profile(true)
+        .output_color_profile(ColorProfile::Encoding(ColorEncoding::Srgb))
+        .build()?;
+    let (Metadata { icc_profile, .. }, _) = decoder.decode_with::<u8>(super::SAMPLE_JXL)?;
+    let icc = lcms2::Profile::new_icc(&icc_profile.expect("ICC profile not retrieved"))?;
+    assert_eq!(icc.color_space(), lcms2::ColorSpaceSignature::RgbData);
+
+    let srgb = lcms2::Profile::new_srgb().icc()?;
+    let decoder = decoder_builder()
+        .output_color_profile(ColorProfile::Icc(srgb))
+        .build()?;
+    let (Metadata { width, height, .. }, data) = decoder.decode_with::<u8>(super::SAMPLE_JXL)?;
+    assert_eq!(data.len(), (width * height * 4) as usize);
+
+    let decoder = decoder_builder()
+        .preferred_color_profile(ColorEncoding::DisplayP3)
+        .build()?;
+    decoder.decode(super::SAMPLE_JXL)?;
+
+    Ok(())
+}
+
+#[test]
+fn cms() -> TestResult {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use crate::{
+        cms::{Cms, CmsProfile, CmsTransform},
+        decode::ColorProfile,
+        encode::ColorEncoding,
+    };
+
+    struct Copy;
+
+    impl CmsTransform for Copy {
+        fn run(&self, _thread: usize, input: &[f32], output: &mut [f32]) -> bool {
+            output.copy_from_slice(input);
+            true
+        }
+    }
+
+    #[derive(Default)]
+    struct Identity(AtomicBool);
+
+    impl Cms for Identity {
+        fn init(
+            &self,
+            _num_threads: usize,
+            _pixels_per_thread: usize,
+            input: &CmsProfile,
+            output: &CmsProfile,
+            _intensity_target: f32,
+        ) -> Option<Box<dyn CmsTransform>> {
+            self.0.store(true, Ordering::Relaxed);
+            (input.num_channels == output.num_channels).then(|| Box::new(Copy) as _)
+        }
+    }
+
+    let cms = Identity::default();
+    let decoder = decoder_builder()
+        .cms(&cms)
+        .output_color_profile(ColorProfile::Encoding(ColorEncoding::DisplayP3))
+        .build()?;
+    let (Metadata { width, height, .. }, data) = decoder.decode_with::<f32>(super::SAMPLE_JXL)?;
+    assert_eq!(data.len(), (width * height * 4) as usize);
+    assert!(cms.0.load(Ordering::Relaxed));
+
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "lcms2")]
+fn lcms2_cms() -> TestResult {
+    use crate::{cms::Lcms2, decode::ColorProfile, encode::ColorEncoding};
+
+    let threads_runner = ThreadsRunner::default();
+    let decoder = decoder_builder()
+        .parallel_runner(&threads_runner)
+        .cms(&Lcms2)
+        .output_color_profile(ColorProfile::Encoding(ColorEncoding::DisplayP3))
+        .build()?;
+    let (Metadata { width, height, .. }, data) = decoder.decode_with::<u8>(super::SAMPLE_JXL)?;
+    assert_eq!(data.len(), (width * height * 4) as usize);
+
+    let decoder = decoder_builder()
+        .cms(&Lcms2)
+        .output_color_profile(ColorProfile::Icc(lcms2::Profile::new_srgb().icc()?))
+        .build()?;
+    let (_, data) = decoder.decode_with::<f32>(super::SAMPLE_JXL)?;
+    assert_eq!(data.len(), (width * height * 4) as usize);
+
+    Ok(())
+}
+
+#[test]
+fn limits() -> TestResult {
+    let info = decoder_builder().build()?.probe(super::SAMPLE_JXL)?;
+    let pixels = u64::from(info.width) * u64::from(info.height);
+
+    // Limits the image meets
+    let decoder = decoder_builder()
+        .max_width(info.width)
+        .max_height(info.height)
+        .max_pixels(pixels)
+        .max_frames(1)
+        .max_extra_channels(1)
+        .build()?;
+    decoder.decode(super::SAMPLE_JXL)?;
+
+    let check = |decoder: crate::decode::JxlDecoder, expected: LimitKind| {
+        let res = decoder.decode(super::SAMPLE_JXL);
+        assert!(
+            matches!(res, Err(DecodeError::LimitExceeded { kind, .. }) if kind == expected),
+            "{expected}"
+        );
+    };
+    check(
+        decoder_builder().max_width(info.width - 1).build()?,
+        LimitKind::Width,
+    );
+    check(
+        decoder_builder().max_height(info.height - 1).build()?,
+        LimitKind::Height,
+    );
+    check(
+        decoder_builder().max_pixels(pixels - 1).build()?,
+        LimitKind::Pixels,
+    );
+    check(decoder_builder().max_frames(0).build()?, LimitKind::Frames);
+    check(
+        decoder_builder().max_extra_channels(0).build()?,
+        LimitKind::ExtraChannels,
+    );
+    check(
+        decoder_builder()
+            .icc_profile(true)
+            .max_icc_size(1)
+            .build()?,
+        LimitKind::IccSize,
+    );
+    check(
+        decoder_builder().max_output_bytes(1024).build()?,
+        LimitKind::OutputBytes,
+    );
+
+    // Checked before decoding the pixels
+    let res = decoder_builder()
+        .max_width(1)
+        .build()?
+        .probe(super::SAMPLE_JXL);
+    assert!(matches!(res, Err(DecodeError::LimitExceeded { .. })));
+
+    Ok(())
+}
+
+#[test]
+fn limits_animation() -> TestResult {
+    let (width, height) = (64u32, 64u32);
+    let data = vec![0u8; (width * height * 3) as usize];
+    let frame = crate::encode::EncoderFrame::new(&data).duration(1);
+    let mut encoder = crate::encoder_builder()
+        .lossless(true)
+        .animation(crate::encode::Animation::default())
+        .build()?;
+    let result: crate::encode::EncoderResult<u8> = encoder
+        .multiple(width, height)?
+        .add_frame(&frame)?
+        .add_frame(&frame)?
+        .add_frame(&frame)?
+        .encode()?;
+
+    // The pixels of every frame are decoded into the same buffer
+    let decoder = decoder_builder().max_output_bytes(data.len()).build()?;
+    let (_, pixels) = decoder.decode_with::<u8>(&result)?;
+    assert_eq!(pixels.len(), data.len());
+
+    let decoder = decoder_builder().max_output_bytes(data.len() - 1).build()?;
+    assert!(matches!(
+        decoder.decode_with::<u8>(&result),
+        Err(DecodeError::LimitExceeded { .. })
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn cancel() -> TestResult {
+    use crate::CancellationToken;
+
+    let token = CancellationToken::new();
+    let decoder = decoder_builder().cancel(token.clone()).build()?;
+    decoder.decode(super::SAMPLE_JXL)?;
+
+    // Cancelled in the middle, and no more rows are passed
+    let mut rows = 0;
+    let res = decoder.decode_rows::<u16>(super::SAMPLE_JXL, |_, _, _| {
+        rows += 1;
+        token.cancel();
+    });
+    assert!(matches!(res, Err(DecodeError::Cancelled)));
+    assert_eq!(rows, 1);
+
+    // Stays cancelled
+    assert!(matches!(
+        decoder.decode(super::SAMPLE_JXL),
+        Err(DecodeError::Cancelled)
+    ));
+
+    Ok(())
+}
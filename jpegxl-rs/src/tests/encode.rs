@@ -16,7 +16,6 @@
  */
 
 use half::f16;
-use image::DynamicImage;
 use jpegxl_sys::color::color_encoding::{
     JxlColorEncoding, JxlColorSpace, JxlPrimaries, JxlRenderingIntent, JxlTransferFunction,
     JxlWhitePoint,
@@ -33,10 +32,7 @@ use crate::{
 use crate::{ResizableRunner, ThreadsRunner, encode::EncoderSpeed};
 use std::thread;
 
-fn get_sample() -> DynamicImage {
-    image::load_from_memory_with_format(super::SAMPLE_PNG, image::ImageFormat::Png)
-        .expect("Failed to get sample file")
-}
+use super::get_sample;
 
 #[test]
 fn simple() -> TestResult {
@@ -386,7 +382,13 @@ fn all_color_encodings() -> TestResult {
     let sample = get_sample().to_rgb8();
     let decoder = decoder_builder().build()?;
 
-    let encodings = [ColorEncoding::Srgb, ColorEncoding::LinearSrgb];
+    let encodings = [
+        ColorEncoding::Srgb,
+        ColorEncoding::LinearSrgb,
+        ColorEncoding::DisplayP3,
+        ColorEncoding::Rec2100Pq,
+        ColorEncoding::Rec2100Hlg,
+    ];
 
     for encoding in encodings {
         let mut encoder = encoder_builder().color_encoding(encoding).build()?;
@@ -401,6 +403,26 @@ fn all_color_encodings() -> TestResult {
     Ok(())
 }
 
+#[test]
+#[cfg(feature = "lcms2")]
+fn lcms2_cms() -> TestResult {
+    use crate::cms::Lcms2;
+
+    let sample = get_sample().to_rgb8();
+    let mut encoder = encoder_builder()
+        .cms(&Lcms2)
+        .color_encoding(ColorEncoding::DisplayP3)
+        .build()?;
+    let result: EncoderResult<u8> =
+        encoder.encode(sample.as_raw(), sample.width(), sample.height())?;
+
+    let (meta, _) = decoder_builder().cms(&Lcms2).build()?.decode(&result)?;
+    assert_eq!(meta.width, sample.width());
+    assert_eq!(meta.height, sample.height());
+
+    Ok(())
+}
+
 #[test]
 fn rgba_encoding() -> TestResult {
     let sample = get_sample().to_rgba8();
@@ -482,3 +504,320 @@ fn non_square_images() -> TestResult {
 
     Ok(())
 }
+
+#[test]
+fn cancel() -> TestResult {
+    use crate::{CancellationToken, EncodeError};
+
+    let sample = get_sample().to_rgb8();
+    let token = CancellationToken::new();
+    let mut encoder = encoder_builder().cancel(token.clone()).build()?;
+
+    // Not cancelled yet
+    let _: EncoderResult<u8> = encoder.encode(sample.as_raw(), sample.width(), sample.height())?;
+
+    token.cancel();
+    let res = encoder.encode::<u8, u8>(sample.as_raw(), sample.width(), sample.height());
+    assert!(matches!(res, Err(EncodeError::Cancelled)));
+
+    Ok(())
+}
+
+#[test]
+fn stream_output() -> TestResult {
+    use crate::{
+        EncodeError,
+        encode::{EncoderOutput, SeekOutput, WriteOutput},
+    };
+    use std::io::{self, Cursor};
+
+    struct FailingOutput;
+
+    impl EncoderOutput for FailingOutput {
+        fn write(&mut self, _data: &[u8]) -> io::Result<()> {
+            Err(io::ErrorKind::BrokenPipe.into())
+        }
+    }
+
+    let sample = get_sample().to_rgb8();
+    let frame = EncoderFrame::new(sample.as_raw());
+    let decoder = decoder_builder().build()?;
+    let mut encoder = encoder_builder().build()?;
+
+    let mut output = WriteOutput::new(vec![]);
+    encoder.encode_frame_to::<u8, u8>(&frame, sample.width(), sample.height(), &mut output)?;
+    let (meta, _) = decoder.decode(&output.into_inner())?;
+    assert_eq!(meta.width, sample.width());
+
+    // Starting after existing data
+    let mut cursor = Cursor::new(vec![0xaa; 4]);
+    cursor.set_position(4);
+    let mut output = SeekOutput::new(cursor)?;
+    assert!(output.seekable());
+    encoder.encode_frame_to::<u8, u8>(&frame, sample.width(), sample.height(), &mut output)?;
+    let data = output.into_inner().into_inner();
+    assert_eq!(data[..4], [0xaa; 4]);
+    decoder.decode(&data[4..])?;
+
+    let mut output = SeekOutput::new(Cursor::new(vec![]))?;
+    encoder.encode_jpeg_to(super::SAMPLE_JPEG, &mut output)?;
+    decoder.decode(output.into_inner().get_ref())?;
+
+    let mut output = WriteOutput::new(vec![]);
+    encoder
+        .multiple::<u8>(sample.width(), sample.height())?
+        .add_frame(&frame)?
+        .add_frame(&frame)?
+        .encode_to(&mut output)?;
+    decoder.decode(&output.into_inner())?;
+
+    let res =
+        encoder.encode_frame_to::<u8, u8>(&frame, sample.width(), sample.height(), FailingOutput);
+    assert!(matches!(res, Err(EncodeError::Io(_))));
+
+    // The encoder is still usable
+    let _: EncoderResult<u8> = encoder.encode(sample.as_raw(), sample.width(), sample.height())?;
+
+    Ok(())
+}
+
+#[test]
+fn chunked() -> TestResult {
+    use crate::{
+        EncodeError,
+        decode::ExtraChannelType,
+        encode::{Buffering, ChunkedSource, ExtraChannel, Rect, WriteOutput},
+    };
+    use image::{GrayImage, RgbImage, RgbaImage};
+
+    fn crop(data: &[u8], width: u32, channels: usize, rect: Rect) -> Vec<u8> {
+        data.chunks_exact(width as usize * channels)
+            .skip(rect.y)
+            .take(rect.height)
+            .flat_map(|row| &row[rect.x * channels..(rect.x + rect.width) * channels])
+            .copied()
+            .collect()
+    }
+
+    struct Tiles(RgbImage);
+
+    impl ChunkedSource for Tiles {
+        type Pixel = u8;
+
+        fn color_data(&mut self, rect: Rect) -> Vec<u8> {
+            crop(self.0.as_raw(), self.0.width(), 3, rect)
+        }
+
+        fn extra_channel_data(&mut self, _index: usize, _rect: Rect) -> Vec<u8> {
+            Vec::new()
+        }
+    }
+
+    struct WithDepth {
+        color: RgbaImage,
+        depth: GrayImage,
+        requested: Vec<usize>,
+    }
+
+    impl ChunkedSource for WithDepth {
+        type Pixel = u8;
+
+        fn num_channels(&self) -> u32 {
+            4
+        }
+
+        fn color_data(&mut self, rect: Rect) -> Vec<u8> {
+            crop(self.color.as_raw(), self.color.width(), 4, rect)
+        }
+
+        fn extra_channel_data(&mut self, index: usize, rect: Rect) -> Vec<u8> {
+            self.requested.push(index);
+            crop(self.depth.as_raw(), self.depth.width(), 1, rect)
+        }
+    }
+
+    struct Short;
+
+    impl ChunkedSource for Short {
+        type Pixel = u16;
+
+        fn color_data(&mut self, _rect: Rect) -> Vec<u16> {
+            vec![0; 3]
+        }
+
+        fn extra_channel_data(&mut self, _index: usize, _rect: Rect) -> Vec<u16> {
+            Vec::new()
+        }
+    }
+
+    let sample = get_sample().to_rgb8();
+    let (width, height) = sample.dimensions();
+    let decoder = decoder_builder().build()?;
+
+    let mut encoder = encoder_builder()
+        .lossless(true)
+        .uses_original_profile(true)
+        .build()?;
+    let result: EncoderResult<u8> = encoder.encode_chunked(Tiles(sample.clone()), width, height)?;
+    let (_, pixels) = decoder.decode_with::<u8>(&result)?;
+    assert_eq!(pixels, sample.as_raw().as_slice());
+
+    let mut encoder = encoder_builder().buffering(Buffering::Stream).build()?;
+    let mut output = WriteOutput::new(vec![]);
+    encoder.encode_chunked_to::<_, u8>(Tiles(sample), width, height, &mut output)?;
+    let (meta, _) = decoder.decode(&output.into_inner())?;
+    assert_eq!((meta.width, meta.height), (width, height));
+
+    let res = encoder.encode_chunked::<_, u16>(Short, 64, 64);
+    assert!(matches!(res, Err(EncodeError::BadInput)));
+
+    // The alpha channel is taken from the color data, only the depth channel is requested
+    let mut source = WithDepth {
+        color: get_sample().to_rgba8(),
+        depth: get_sample().to_luma8(),
+        requested: vec![],
+    };
+    let mut encoder = encoder_builder()
+        .has_alpha(true)
+        .lossless(true)
+        .extra_channels(vec![ExtraChannel::new(ExtraChannelType::Depth)])
+        .build()?;
+    let result: EncoderResult<u8> = encoder.encode_chunked(&mut source, width, height)?;
+    source.requested.dedup();
+    assert_eq!(source.requested, [1]);
+
+    let (meta, _, planes) = decoder.decode_extra_channels_with::<u8>(&result)?;
+    assert_eq!(meta.extra_channels[1].channel_type, ExtraChannelType::Depth);
+    assert_eq!(planes[1], source.depth.as_raw().as_slice());
+
+    Ok(())
+}
+
+#[test]
+#[allow(clippy::float_cmp)]
+fn extra_channels() -> TestResult {
+    use crate::{EncodeError, decode::ExtraChannelType, encode::ExtraChannel};
+
+    let sample = get_sample().to_rgba8();
+    let (width, height) = sample.dimensions();
+    let depth = get_sample().to_luma8();
+    let thermal: Vec<u8> = depth.iter().map(|v| 255 - v).collect();
+    let spot = vec![128u8; depth.len()];
+
+    let mut encoder = encoder_builder()
+        .has_alpha(true)
+        .lossless(true)
+        .extra_channels(vec![
+            ExtraChannel::new(ExtraChannelType::Depth).name("depth"),
+            ExtraChannel::new(ExtraChannelType::Thermal)
+                .name("thermal")
+                .distance(0.0),
+            ExtraChannel::spot_color([1.0, 0.0, 0.0], 0.5),
+        ])
+        .build()?;
+    let frame = EncoderFrame::new(sample.as_raw())
+        .num_channels(4)
+        .extra_channel(depth.as_raw())
+        .extra_channel(&thermal)
+        .extra_channel(&spot);
+    let result: EncoderResult<u8> = encoder.encode_frame(&frame, width, height)?;
+
+    let (meta, _, planes) = decoder_builder()
+        .build()?
+        .decode_extra_channels_with::<u8>(&result)?;
+    let types: Vec<_> = meta
+        .extra_channels
+        .iter()
+        .map(|info| info.channel_type)
+        .collect();
+    assert_eq!(
+        types,
+        [
+            ExtraChannelType::Alpha,
+            ExtraChannelType::Depth,
+            ExtraChannelType::Thermal,
+            ExtraChannelType::SpotColor
+        ]
+    );
+    assert_eq!(meta.extra_channels[1].name, "depth");
+    assert_eq!(meta.extra_channels[2].name, "thermal");
+    assert_eq!(meta.extra_channels[3].spot_color, [1.0, 0.0, 0.0, 0.5]);
+    assert_eq!(planes[1], depth.as_raw().as_slice());
+    assert_eq!(planes[2], thermal);
+    assert_eq!(planes[3], spot);
+
+    // Missing extra channel data
+    let res = encoder.encode_frame::<u8, u8>(
+        &EncoderFrame::new(sample.as_raw()).num_channels(4),
+        width,
+        height,
+    );
+    assert!(matches!(res, Err(EncodeError::BadInput)));
+
+    Ok(())
+}
+
+#[test]
+#[allow(clippy::float_cmp)]
+fn animation() -> TestResult {
+    use crate::decode::Pixels;
+    use crate::encode::Animation;
+
+    let (width, height) = (64, 64);
+    let colors = [[255u8, 0, 0], [0, 255, 0], [0, 0, 255]];
+    let frames: Vec<Vec<u8>> = colors
+        .iter()
+        .map(|color| color.repeat((width * height) as usize))
+        .collect();
+    let names = [Some("red"), None, Some("blue")];
+
+    let mut encoder = encoder_builder()
+        .lossless(true)
+        .animation(Animation {
+            num_loops: 2,
+            have_timecodes: true,
+            ..Animation::default()
+        })
+        .build()?;
+    let mut multiple = encoder.multiple::<u8>(width, height)?;
+    for (i, (data, name)) in (1..).zip(frames.iter().zip(names)) {
+        let frame = EncoderFrame::new(data).duration(i * 10).timecode(i);
+        multiple = multiple.add_frame(&match name {
+            Some(name) => frame.name(name),
+            None => frame,
+        })?;
+    }
+    let result = multiple.encode()?;
+
+    let mut decoder = decoder_builder().build()?;
+    let mut frames_iter = decoder.frames(&result)?;
+    let mut count = 0;
+    for ((frame, name), i) in frames_iter.by_ref().zip(names).zip(1..) {
+        let (info, _) = frame?;
+        assert_eq!(info.duration, i * 10);
+        assert_eq!(info.duration_ms, f64::from(i) * 100.0);
+        assert_eq!(info.timecode, i);
+        assert_eq!(info.name, name.unwrap_or_default());
+        count += 1;
+    }
+    assert_eq!(count, 3);
+
+    let animation = frames_iter
+        .basic_info()
+        .expect("No basic info")
+        .animation
+        .clone();
+    assert_eq!(animation.tps_numerator, 100);
+    assert_eq!(animation.num_loops, 2);
+
+    // Seek back to a frame
+    let (info, pixels) = frames_iter.frame(1).expect("Frame not found")?;
+    assert!(info.name.is_empty());
+    let Pixels::Uint8(pixels) = pixels else {
+        panic!("Unexpected pixel type");
+    };
+    assert_eq!(pixels, frames[1]);
+    assert!(frames_iter.frame(3).is_none());
+
+    Ok(())
+}
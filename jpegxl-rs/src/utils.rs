@@ -19,6 +19,11 @@ along with jpegxl-rs.  If not, see <https://www.gnu.org/licenses/>.
 
 use jpegxl_sys::decode::{JxlSignature, JxlSignatureCheck};
 
+use crate::{
+    decode::{ImageInfo, JxlDecoder},
+    errors::DecodeError,
+};
+
 /// Check if the signature of the input is valid.
 /// Return `None` if it needs more data.
 #[must_use]
@@ -32,6 +37,15 @@ pub fn check_valid_signature(buf: &[u8]) -> Option<bool> {
     }
 }
 
+/// Read the header information of a JPEG XL image, without decoding the pixels.
+/// See [`JxlDecoder::probe`] for details.
+///
+/// # Errors
+/// Return a [`DecodeError`] if it fails to create a decoder or to read the header
+pub fn read_info(data: &[u8]) -> Result<ImageInfo, DecodeError> {
+    JxlDecoder::builder().build()?.probe(data)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -46,6 +60,21 @@ mod tests {
         assert_eq!(check_valid_signature(SAMPLE_JXL), Some(true));
     }
 
+    #[test]
+    fn test_read_info() -> testresult::TestResult {
+        let info = read_info(SAMPLE_JXL)?;
+        assert_eq!(info.num_color_channels, 3);
+        assert!(info.has_alpha_channel);
+        assert!(info.animation.is_none());
+
+        assert!(matches!(
+            read_info(&[0; 64]),
+            Err(DecodeError::InvalidInput)
+        ));
+
+        Ok(())
+    }
+
     #[test]
     fn test_signature_partial_data() {
         assert!(check_valid_signature(&[0]).is_none());
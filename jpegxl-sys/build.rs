@@ -31,6 +31,7 @@ fn main() {
             println!("cargo:rustc-link-search=native={path}");
             println!("cargo:rustc-link-lib=jxl");
             println!("cargo:rustc-link-lib=jxl_threads");
+            println!("cargo:rustc-link-lib=jxl_cms");
         } else {
             pkg_config::Config::new()
                 .atleast_version(version)
@@ -42,6 +43,10 @@ fn main() {
                 .unwrap_or_else(|_| {
                     panic!("Cannot find `libjxl_threads` with version >= {version}")
                 });
+            pkg_config::Config::new()
+                .atleast_version(version)
+                .probe("libjxl_cms")
+                .unwrap_or_else(|_| panic!("Cannot find `libjxl_cms` with version >= {version}"));
         }
     } else {
         #[cfg(feature = "vendored")]
@@ -39,8 +39,8 @@ pub type JpegXlCmsSetFieldsFromIccFunc = extern "C-unwind" fn(
 #[repr(C)]
 #[derive(Debug, Clone)]
 pub struct JxlColorProfileIcc {
-    data: *const u8,
-    size: usize,
+    pub data: *const u8,
+    pub size: usize,
 }
 
 #[repr(C)]